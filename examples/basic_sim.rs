@@ -1,8 +1,10 @@
-use simlib::{Executor, Phase, Recorder};
+use simlib::{Executor, Phase, Recordable, Recorder};
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Recordable)]
 struct Simulation {
+	#[record(unit = "m")]
 	position: (f64, f64),
+	#[record(unit = "m/s")]
 	velocity: (f64, f64),
 }
 
@@ -16,13 +18,10 @@ fn main() {
 	let end_time = 5.0;
 
 	let mut recorder = Recorder::<Simulation>::new("test-sim.csv");
-	recorder.track("position_x", |sim| sim.position.0);
-	recorder.track("position_y", |sim| sim.position.1);
-	recorder.track("velocity_x", |sim| sim.velocity.0);
-	recorder.track("velocity_y", |sim| sim.velocity.1);
+	recorder.track_all("");
 
 	let mut exec = Executor::<Simulation>::new(dt, end_time);
-	exec.set_recorder(recorder);
+	exec.add_recorder(recorder);
 
 	exec.add_job(Phase::Init, |sim, time| {
 		println!("Starting simulation at t={} with sim={:?}", time.t, sim);
@@ -37,7 +36,7 @@ fn main() {
 				sim.velocity.1,
 			]
 		},
-		|sim, _| vec![sim.velocity.0, sim.velocity.1, 0.0, -9.81],
+		|sim, _, _| vec![sim.velocity.0, sim.velocity.1, 0.0, -9.81],
 		|sim, s| {
 			sim.position = (s[0], s[1]);
 			sim.velocity = (s[2], s[3]);
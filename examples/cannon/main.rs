@@ -12,25 +12,35 @@ struct CannonSim {
 }
 
 fn main() {
-	let cannon_sim = CannonSim {
+	let mut exec = executor();
+
+	let filename = "cannon-sim.csv";
+	let mut recorder = Recorder::<CannonSim>::new(filename);
+	track_outputs(&mut recorder);
+	exec.add_recorder(recorder);
+
+	exec.run(initial_state());
+
+	println!("Simulation data saved to {filename}");
+}
+
+fn initial_state() -> CannonSim {
+	CannonSim {
 		cannon: Cannon::new(PI / 3.0, 50.0),
-	};
+	}
+}
+
+fn track_outputs(recorder: &mut Recorder<CannonSim>) {
+	recorder.track_vec("pos", |sim| sim.cannon.pos);
+	recorder.track_vec("vel", |sim| sim.cannon.vel);
+	recorder.track_vec("acc", |sim| sim.cannon.acc);
+}
 
+fn executor() -> Executor<CannonSim> {
 	let dt = 0.1;
 	let end_time = 10.0;
 	let mut exec = Executor::<CannonSim>::new(dt, end_time);
 
-	let filename = "cannon-sim.csv";
-	let mut recorder = Recorder::<CannonSim>::new(filename);
-	recorder.track("pos_x", |sim| sim.cannon.pos.x);
-	recorder.track("pos_y", |sim| sim.cannon.pos.x);
-	recorder.track("vel_x", |sim| sim.cannon.vel.x);
-	recorder.track("vel_y", |sim| sim.cannon.vel.x);
-	recorder.track("acc_x", |sim| sim.cannon.acc.x);
-	recorder.track("acc_y", |sim| sim.cannon.acc.x);
-
-	exec.set_recorder(recorder);
-
 	exec.set_integrator(
 		|sim, _| {
 			vec![
@@ -40,7 +50,7 @@ fn main() {
 				sim.cannon.vel.y,
 			]
 		},
-		|sim, _| sim.cannon.derivative(),
+		|sim, _, _| sim.cannon.derivative(),
 		|sim, s| {
 			sim.cannon.pos.x = s[0];
 			sim.cannon.pos.y = s[1];
@@ -68,7 +78,24 @@ fn main() {
 		println!("==================================================");
 	});
 
-	exec.run(cannon_sim);
+	exec
+}
 
-	println!("Simulation data saved to {filename}");
+#[cfg(test)]
+mod tests {
+	use simlib::Comparison;
+
+	use super::*;
+
+	#[test]
+	fn matches_golden_run() {
+		let mut exec = executor();
+		let mut recorder = Recorder::in_memory();
+		track_outputs(&mut recorder);
+		exec.add_recorder(recorder);
+		let result = exec.run(initial_state());
+
+		let golden = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/cannon/golden.csv");
+		Comparison::new().assert_golden(golden, &result.recordings[0]);
+	}
 }
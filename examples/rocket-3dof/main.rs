@@ -1,7 +1,9 @@
 // TODO: remove this once sim is finished
 #![allow(dead_code)]
 
-use simlib::{Executor, Phase, Recorder};
+use simlib::{
+	Angle, Executor, Length, NonFiniteAction, Phase, Plot, Recorder, Reduction, RunStatus, Value,
+};
 
 use crate::{
 	aero::BodyAeroCoefficients,
@@ -19,8 +21,8 @@
 fn main() {
 	let motor = Motor::from_eng_file("I280.eng").unwrap();
 	let rail = Rail {
-		angle: 85_f64.to_radians(),
-		length: 3.084, // 10 feet
+		angle: Angle::from_degrees(85.0).as_radians(),
+		length: Length::from_feet(10.0).as_meters(),
 	};
 
 	// this is modeled roughly after an IRIS 4 rocket with an H/I motor
@@ -48,6 +50,7 @@ fn main() {
 	let dt = 0.01;
 	let end_time = 30.0;
 	let mut exec = Executor::<Rocket>::new(dt, end_time);
+	exec.set_non_finite_action(NonFiniteAction::Stop);
 
 	exec.set_integrator(
 		|sim, _| {
@@ -60,7 +63,7 @@ fn main() {
 				sim.angular_vel,
 			]
 		},
-		|sim, time| sim.derivative(time),
+		|sim, time, updates| sim.derivative(time, updates),
 		|sim, s| {
 			sim.position.x = s[0];
 			sim.position.y = s[1];
@@ -98,6 +101,8 @@ fn main() {
 		);
 	});
 
+	exec.set_event_log_file("rocket-3dof-events.jsonl");
+	let events = exec.event_log();
 	let mut prev_phase = FlightPhase::OnRail;
 	exec.add_job(Phase::PostIntegrate, move |sim, time| {
 		if sim.flight_phase == FlightPhase::Boost && time.t > sim.motor.burn_time_end {
@@ -111,24 +116,108 @@ fn main() {
 		}
 
 		if sim.flight_phase != prev_phase {
-			println!("Phase {:?}, t = {}", sim.flight_phase, time.t);
+			let speed = Value::F64(sim.velocity.length());
+			let altitude = Value::F64(sim.position.y);
+			match sim.flight_phase {
+				FlightPhase::OnRail => {},
+				FlightPhase::Boost => events.info("RailDeparture", &[("velocity", speed)]),
+				FlightPhase::Coast => events.info("Burnout", &[("velocity", speed)]),
+				FlightPhase::Descent => events.info("Apogee", &[("altitude", altitude)]),
+				FlightPhase::Ground => {
+					events.info("Landing", &[("range", Value::F64(sim.position.x))])
+				},
+			}
 			prev_phase = sim.flight_phase;
 		}
 	});
 
 	let mut recorder = Recorder::<Rocket>::new("rocket-3dof.csv");
-	recorder.track("pos_x", |sim| sim.position.x);
-	recorder.track("pos_y", |sim| sim.position.y);
-	recorder.track("vel_x", |sim| sim.velocity.x);
-	recorder.track("vel_y", |sim| sim.velocity.y);
-	recorder.track("acc_x", |sim| sim.acceleration.x);
-	recorder.track("acc_y", |sim| sim.acceleration.y);
-	recorder.track("orientation", |sim| sim.orientation);
-	recorder.track("angular_vel", |sim| sim.angular_vel);
-	recorder.track("angular_accel", |sim| sim.angular_accel);
+	recorder.set_keep_in_memory(true);
+	recorder.track("pos_x", |sim| sim.position.x).unit("m");
+	recorder.track("pos_y", |sim| sim.position.y).unit("m");
+	recorder.track("vel_x", |sim| sim.velocity.x).unit("m/s");
+	recorder.track("vel_y", |sim| sim.velocity.y).unit("m/s");
+	recorder
+		.track("acc_x", |sim| sim.acceleration.x)
+		.unit("m/s^2");
+	recorder
+		.track("acc_y", |sim| sim.acceleration.y)
+		.unit("m/s^2");
+	recorder
+		.track("orientation", |sim| sim.orientation)
+		.unit("rad")
+		.description("angle from horizontal, counterclockwise positive");
+	recorder
+		.track("angular_vel", |sim| sim.angular_vel)
+		.unit("rad/s");
+	recorder
+		.track("angular_accel", |sim| sim.angular_accel)
+		.unit("rad/s^2");
 	recorder.track("mach", |sim| velocity_to_mach(sim.velocity.length(), sim.position.y));
 
-	exec.set_recorder(recorder);
+	exec.add_recorder(recorder);
+
+	let mut summary = Recorder::<Rocket>::new("rocket-3dof-summary.csv");
+	summary.set_rate(10.0);
+	summary.track("altitude", |sim| sim.position.y).unit("m");
+	summary
+		.track("speed", |sim| sim.velocity.length())
+		.unit("m/s");
+	summary.track_enum(
+		"phase",
+		&[
+			FlightPhase::OnRail,
+			FlightPhase::Boost,
+			FlightPhase::Coast,
+			FlightPhase::Descent,
+			FlightPhase::Ground,
+		],
+		|sim| sim.flight_phase,
+	);
+	exec.add_recorder(summary);
+
+	exec.add_metric("apogee", Reduction::Max, |sim| sim.position.y)
+		.unit("m");
+	exec.add_metric("apogee_time", Reduction::TimeOfMax, |sim| sim.position.y);
+	exec.add_metric("max_mach", Reduction::Max, |sim| {
+		velocity_to_mach(sim.velocity.length(), sim.position.y)
+	});
+	exec.add_metric("max_q", Reduction::Max, dynamic_pressure)
+		.unit("Pa");
+	exec.add_metric("max_q_time", Reduction::TimeOfMax, dynamic_pressure);
+	exec.add_metric("rail_exit_velocity", Reduction::AtEvent("RailDeparture".to_string()), |sim| {
+		sim.velocity.length()
+	})
+	.unit("m/s");
+
+	let result = exec.run(sim);
+	for event in &result.events {
+		let fields: Vec<String> = event
+			.fields
+			.iter()
+			.map(|(key, value)| format!("{key}={:.3}", value.as_f64()))
+			.collect();
+		println!("t={:.3} {} {}", event.t, event.name, fields.join(" "));
+	}
+	println!("{}", result.summary);
+
+	let run = &result.recordings[0];
+	Plot::new("Altitude")
+		.time_history(run, "pos_y")
+		.events(&result.events)
+		.save("rocket-3dof-altitude.svg")
+		.unwrap();
+	Plot::new("Trajectory")
+		.xy(run, "pos_x", "pos_y")
+		.save("rocket-3dof-trajectory.svg")
+		.unwrap();
+
+	if let RunStatus::NonFinite(non_finite) = result.status {
+		println!("Sim stopped early: {non_finite}");
+	}
+}
 
-	exec.run(sim);
+fn dynamic_pressure(sim: &Rocket) -> f64 {
+	let density = atmosphere::get_air_density(Length::from_meters(sim.position.y));
+	0.5 * density * sim.velocity.length_squared()
 }
@@ -1,5 +1,5 @@
 use glam::{DMat2, DVec2, dvec2};
-use simlib::SimTime;
+use simlib::{Deferred, Length, SimTime};
 
 use crate::{aero::BodyAeroCoefficients, atmosphere, motor::Motor};
 
@@ -71,7 +71,7 @@ fn get_aero_force_body(&self) -> DVec2 {
 		let ca = self.coeffs.ca_mach.get(mach);
 		let cn = self.coeffs.cn_alpha_mach.get(mach) * alpha;
 
-		let rho = atmosphere::get_air_density(self.position.y);
+		let rho = atmosphere::get_air_density(Length::from_meters(self.position.y));
 		let s = self.coeffs.surface_area;
 
 		let aero_load = 0.5 * rho * (v * v) * s;
@@ -79,7 +79,7 @@ fn get_aero_force_body(&self) -> DVec2 {
 		aero_load * dvec2(-ca, cn)
 	}
 
-	pub fn derivative(&mut self, time: &SimTime) -> Vec<f64> {
+	pub fn derivative(&self, time: &SimTime, updates: &mut Deferred<Self>) -> Vec<f64> {
 		let body_to_lcef_dcm = DMat2::from_angle(self.orientation);
 
 		// translational forces
@@ -112,7 +112,7 @@ pub fn derivative(&mut self, time: &SimTime) -> Vec<f64> {
 				];
 			}
 
-			self.flight_phase = FlightPhase::Boost;
+			updates.defer(|rocket| rocket.flight_phase = FlightPhase::Boost);
 		}
 
 		let normal_force = aero_force_body.y;
@@ -132,12 +132,10 @@ pub fn derivative(&mut self, time: &SimTime) -> Vec<f64> {
 
 pub fn velocity_to_mach(vel: f64, altitude_m: f64) -> f64 {
 	const GAMMA: f64 = 1.4; // adiabatic index of air
-	const R_AIR: f64 = 287.05; // ideal gas constant for air
 
-	let temperature_degc = atmosphere::get_temperature(altitude_m);
-	let temperature_k = temperature_degc + 273.15;
+	let temperature = atmosphere::get_temperature(Length::from_meters(altitude_m));
 
-	let speed_of_sound = f64::sqrt(GAMMA * R_AIR * temperature_k);
+	let speed_of_sound = f64::sqrt(GAMMA * atmosphere::R_AIR * temperature.as_kelvin());
 
 	vel / speed_of_sound
 }
@@ -1,4 +1,5 @@
 use glam::{DMat3, DQuat, DVec3};
+use simlib::Length;
 
 use crate::{aero::BodyAeroCoefficients, atmosphere, motor::Motor};
 
@@ -27,7 +28,7 @@ pub fn get_aero_force_body(&self) -> DVec3 {
 		let lcef_to_body_dcm = DMat3::from_quat(self.orientation).transpose();
 		let v_body = lcef_to_body_dcm * self.velocity;
 
-		let rho = atmosphere::get_air_density(self.position.x);
+		let rho = atmosphere::get_air_density(Length::from_meters(self.position.x));
 		let v = self.velocity.length();
 		let s = self.coeffs.surface_area;
 
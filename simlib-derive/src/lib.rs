@@ -0,0 +1,139 @@
+//! Derive macros for simlib.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, LitStr, parse_macro_input, spanned::Spanned};
+
+/// Derives `simlib::Recordable`, exposing the fields of a struct as recordable variables.
+///
+/// Each field's variables are named after the field, so nested structs produce hierarchical names
+/// like `velocity.x`. Every field's type must implement `Recordable` unless the field is skipped.
+/// Fieldless enums are recorded as enum channels named after their variants.
+///
+/// Fields accept these attributes:
+///
+/// - `#[record(unit = "m/s")]` sets the unit of every variable of the field.
+/// - `#[record(skip)]` leaves the field out.
+/// - `#[record(rename = "v")]` names the field's variables after `v` instead.
+#[proc_macro_derive(Recordable, attributes(record))]
+pub fn derive_recordable(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	match expand(&input) {
+		Ok(tokens) => tokens.into(),
+		Err(error) => error.to_compile_error().into(),
+	}
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+	let name = &input.ident;
+	let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+	let body = match &input.data {
+		Data::Struct(data) => expand_struct(&data.fields)?,
+		Data::Enum(data) => {
+			let mut names = Vec::new();
+			let mut arms = Vec::new();
+			for (index, variant) in data.variants.iter().enumerate() {
+				if !matches!(variant.fields, Fields::Unit) {
+					return Err(syn::Error::new(
+						variant.span(),
+						"only enums without fields can be recorded",
+					));
+				}
+				let ident = &variant.ident;
+				let index = index as u32;
+				names.push(ident.to_string());
+				arms.push(quote! { Self::#ident => #index });
+			}
+			quote! {
+				::std::vec![::simlib::Variable::new(
+					::simlib::ChannelType::Enum(::std::vec![#(#names.to_string()),*]),
+					|value: &Self| ::simlib::Value::Enum(match value { #(#arms),* }),
+				)]
+			}
+		},
+		Data::Union(data) => {
+			return Err(syn::Error::new(data.union_token.span(), "unions cannot be recorded"));
+		},
+	};
+
+	Ok(quote! {
+		impl #impl_generics ::simlib::Recordable for #name #ty_generics #where_clause {
+			fn variables() -> ::std::vec::Vec<::simlib::Variable<Self>> {
+				#body
+			}
+		}
+	})
+}
+
+fn expand_struct(fields: &Fields) -> syn::Result<TokenStream2> {
+	let mut extends = Vec::new();
+	for (index, field) in fields.iter().enumerate() {
+		let attributes = FieldAttributes::parse(field)?;
+		if attributes.skip {
+			continue;
+		}
+
+		let member = match &field.ident {
+			Some(ident) => quote! { #ident },
+			None => {
+				let index = syn::Index::from(index);
+				quote! { #index }
+			},
+		};
+		let name = match (attributes.rename, &field.ident) {
+			(Some(rename), _) => rename,
+			(None, Some(ident)) => ident.to_string(),
+			(None, None) => index.to_string(),
+		};
+		let ty = &field.ty;
+		let with_unit = attributes.unit.map(|unit| quote! { .with_unit(#unit) });
+
+		extends.push(quote! {
+			variables.extend(
+				<#ty as ::simlib::Recordable>::variables()
+					.into_iter()
+					.map(|variable| variable.nest(#name, |value: &Self| &value.#member) #with_unit),
+			);
+		});
+	}
+
+	Ok(quote! {
+		let mut variables = ::std::vec::Vec::new();
+		#(#extends)*
+		variables
+	})
+}
+
+#[derive(Default)]
+struct FieldAttributes {
+	skip: bool,
+	unit: Option<String>,
+	rename: Option<String>,
+}
+
+impl FieldAttributes {
+	fn parse(field: &syn::Field) -> syn::Result<Self> {
+		let mut attributes = Self::default();
+		for attr in field
+			.attrs
+			.iter()
+			.filter(|attr| attr.path().is_ident("record"))
+		{
+			attr.parse_nested_meta(|meta| {
+				if meta.path.is_ident("skip") {
+					attributes.skip = true;
+				} else if meta.path.is_ident("unit") {
+					attributes.unit = Some(meta.value()?.parse::<LitStr>()?.value());
+				} else if meta.path.is_ident("rename") {
+					attributes.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+				} else {
+					return Err(meta.error("expected `skip`, `unit` or `rename`"));
+				}
+				Ok(())
+			})?;
+		}
+		Ok(attributes)
+	}
+}
@@ -0,0 +1,325 @@
+//! Regression comparisons of runs against golden recordings.
+
+use std::{fmt, io, path::Path};
+
+use crate::RunData;
+
+/// Environment variable that makes [`Comparison::assert_golden`] write the golden file instead of
+/// checking it.
+pub const BLESS_VAR: &str = "SIMLIB_BLESS";
+
+/// Compares the channels of a run against a golden run, sample by sample.
+///
+/// The runs are aligned on the golden run's sample times, interpolating the other run linearly if
+/// its samples fall at different times. A sample passes if it differs from the golden value by at
+/// most `abs + rel * |golden|`, and NaN matches only NaN. String channels are not compared.
+#[derive(Clone, Debug)]
+pub struct Comparison {
+	abs: f64,
+	rel: f64,
+	time_tolerance: f64,
+	overrides: Vec<(String, f64, f64)>,
+	ignored: Vec<String>,
+}
+
+impl Default for Comparison {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Comparison {
+	/// Creates a comparison with an absolute tolerance of 1e-12 and a relative tolerance of 1e-9.
+	pub fn new() -> Self {
+		Self {
+			abs: 1e-12,
+			rel: 1e-9,
+			time_tolerance: 1e-9,
+			overrides: Vec::new(),
+			ignored: Vec::new(),
+		}
+	}
+
+	/// Sets the absolute and relative tolerances of every channel without its own.
+	pub fn tolerance(mut self, abs: f64, rel: f64) -> Self {
+		self.abs = abs;
+		self.rel = rel;
+		self
+	}
+
+	/// Sets the absolute and relative tolerances of one channel.
+	pub fn channel_tolerance(mut self, name: &str, abs: f64, rel: f64) -> Self {
+		self.overrides.push((name.to_string(), abs, rel));
+		self
+	}
+
+	/// Sets how far apart the first and last sample times of the runs may be.
+	pub fn time_tolerance(mut self, tolerance: f64) -> Self {
+		self.time_tolerance = tolerance;
+		self
+	}
+
+	/// Leaves a channel out of the comparison.
+	pub fn ignore(mut self, name: &str) -> Self {
+		self.ignored.push(name.to_string());
+		self
+	}
+
+	pub fn compare(&self, golden: &RunData, actual: &RunData) -> ComparisonReport {
+		let span = |run: &RunData| match run.time() {
+			[] => None,
+			time => Some((time[0], time[time.len() - 1])),
+		};
+		let mut report = ComparisonReport {
+			channels: Vec::new(),
+			missing: Vec::new(),
+			extra: Vec::new(),
+			golden_span: span(golden),
+			actual_span: span(actual),
+			time_tolerance: self.time_tolerance,
+		};
+
+		for name in golden.names() {
+			if self.ignored.contains(name) {
+				continue;
+			}
+			let Some(expected) = golden.column(name) else {
+				continue;
+			};
+			if actual.column(name).is_none() {
+				report.missing.push(name.clone());
+				continue;
+			}
+			let (abs, rel) = self.tolerances(name);
+
+			let mut channel = ChannelComparison {
+				name: name.clone(),
+				max_error: 0.0,
+				time_of_max_error: None,
+				first_divergence: None,
+			};
+			for (&t, &expected) in golden.time().iter().zip(expected) {
+				let Some(value) = actual.value_at(name, t) else {
+					continue;
+				};
+				let error = match (expected.is_nan(), value.is_nan()) {
+					(true, true) => 0.0,
+					(false, false) => (value - expected).abs(),
+					_ => f64::INFINITY,
+				};
+				if error > channel.max_error {
+					channel.max_error = error;
+					channel.time_of_max_error = Some(t);
+				}
+				if channel.first_divergence.is_none() && error > abs + rel * expected.abs() {
+					channel.first_divergence = Some(t);
+				}
+			}
+			report.channels.push(channel);
+		}
+
+		report.extra = actual
+			.names()
+			.iter()
+			.filter(|name| !golden.names().contains(name) && !self.ignored.contains(name))
+			.cloned()
+			.collect();
+		report
+	}
+
+	/// Compares two recordings loaded with [`RunData::load`].
+	pub fn compare_files<P, Q>(&self, golden: P, actual: Q) -> io::Result<ComparisonReport>
+	where
+		P: AsRef<Path>,
+		Q: AsRef<Path>,
+	{
+		Ok(self.compare(&RunData::load(golden)?, &RunData::load(actual)?))
+	}
+
+	/// Asserts that a run matches the golden recording at `path`.
+	///
+	/// If the environment variable [`BLESS_VAR`] is set, the run is saved as the new golden
+	/// recording instead, in CSV.
+	///
+	/// # Panics
+	///
+	/// Panics with the report if the run does not match, or if the golden recording cannot be
+	/// loaded.
+	pub fn assert_golden<P: AsRef<Path>>(&self, path: P, actual: &RunData) {
+		let path = path.as_ref();
+		if std::env::var_os(BLESS_VAR).is_some() {
+			actual
+				.save_csv(path)
+				.unwrap_or_else(|e| panic!("failed to write {}: {e}", path.display()));
+			return;
+		}
+
+		let golden = RunData::load(path).unwrap_or_else(|e| {
+			panic!("failed to load {}: {e} (set {BLESS_VAR}=1 to create it)", path.display())
+		});
+		let report = self.compare(&golden, actual);
+		assert!(report.passed(), "run does not match {}:\n{report}", path.display());
+	}
+
+	fn tolerances(&self, name: &str) -> (f64, f64) {
+		match self.overrides.iter().rev().find(|(n, ..)| n == name) {
+			Some((_, abs, rel)) => (*abs, *rel),
+			None => (self.abs, self.rel),
+		}
+	}
+}
+
+/// The result of comparing one channel.
+#[derive(Clone, Debug)]
+pub struct ChannelComparison {
+	pub name: String,
+	/// The largest absolute difference from the golden run, which is infinite if only one of the
+	/// values was NaN.
+	pub max_error: f64,
+	/// The time of the largest difference, or `None` if the channels were identical.
+	pub time_of_max_error: Option<f64>,
+	/// The time of the first sample outside the tolerance.
+	pub first_divergence: Option<f64>,
+}
+
+impl ChannelComparison {
+	pub fn passed(&self) -> bool {
+		self.first_divergence.is_none()
+	}
+}
+
+/// The results of a [`Comparison`].
+#[derive(Clone, Debug)]
+pub struct ComparisonReport {
+	/// Every compared channel, in the golden run's order.
+	pub channels: Vec<ChannelComparison>,
+	/// Channels of the golden run that the other run does not have.
+	pub missing: Vec<String>,
+	/// Channels of the other run that the golden run does not have, which do not fail the
+	/// comparison.
+	pub extra: Vec<String>,
+	/// The first and last sample times of the golden run, or `None` if it has no samples.
+	pub golden_span: Option<(f64, f64)>,
+	pub actual_span: Option<(f64, f64)>,
+	time_tolerance: f64,
+}
+
+impl ComparisonReport {
+	/// Returns true if every channel is within its tolerance, no channel is missing, and the runs
+	/// cover the same times.
+	pub fn passed(&self) -> bool {
+		self.spans_match() && self.missing.is_empty() && self.channels.iter().all(|c| c.passed())
+	}
+
+	/// Returns the earliest time any channel diverged.
+	pub fn first_divergence(&self) -> Option<f64> {
+		self.channels
+			.iter()
+			.filter_map(|c| c.first_divergence)
+			.min_by(f64::total_cmp)
+	}
+
+	pub fn channel(&self, name: &str) -> Option<&ChannelComparison> {
+		self.channels.iter().find(|c| c.name == name)
+	}
+
+	fn spans_match(&self) -> bool {
+		match (self.golden_span, self.actual_span) {
+			(Some((g0, g1)), Some((a0, a1))) => {
+				(g0 - a0).abs() <= self.time_tolerance && (g1 - a1).abs() <= self.time_tolerance
+			},
+			(golden, actual) => golden.is_none() && actual.is_none(),
+		}
+	}
+}
+
+impl fmt::Display for ComparisonReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{:<20} {:>12} {:>10} {:>16}", "channel", "max error", "at", "first diverged")?;
+		let time = |t: Option<f64>| t.map_or("-".to_string(), |t| t.to_string());
+		for channel in &self.channels {
+			writeln!(
+				f,
+				"{:<20} {:>12.3e} {:>10} {:>16}",
+				channel.name,
+				channel.max_error,
+				time(channel.time_of_max_error),
+				time(channel.first_divergence),
+			)?;
+		}
+
+		for name in &self.missing {
+			writeln!(f, "missing channel {name}")?;
+		}
+		for name in &self.extra {
+			writeln!(f, "extra channel {name}")?;
+		}
+		if !self.spans_match() {
+			let span = |span: Option<(f64, f64)>| match span {
+				Some((start, end)) => format!("{start} to {end}"),
+				None => "no samples".to_string(),
+			};
+			writeln!(
+				f,
+				"golden run covers {}, but this run covers {}",
+				span(self.golden_span),
+				span(self.actual_span)
+			)?;
+		}
+
+		match (self.passed(), self.first_divergence()) {
+			(true, _) => write!(f, "passed"),
+			(false, Some(t)) => write!(f, "failed, first diverged at t = {t}"),
+			(false, None) => write!(f, "failed"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn run(f: impl Fn(f64) -> f64, dt: f64) -> RunData {
+		let mut data = RunData::new(&["x", "y"]);
+		for i in 0..=(1.0 / dt).round() as usize {
+			let t = i as f64 * dt;
+			data.push(t, &[f(t), 2.0]);
+		}
+		data
+	}
+
+	#[test]
+	fn reports_divergence_and_max_error() {
+		let golden = run(|t| t, 0.25);
+		let actual = run(|t| if t > 0.6 { t + 0.1 * t } else { t }, 0.125);
+
+		let report = Comparison::new()
+			.tolerance(0.0, 0.05)
+			.compare(&golden, &actual);
+		assert!(!report.passed());
+		assert_eq!(report.first_divergence(), Some(0.75));
+		let x = report.channel("x").unwrap();
+		assert!((x.max_error - 0.1).abs() < 1e-12);
+		assert_eq!(x.time_of_max_error, Some(1.0));
+		assert!(report.channel("y").unwrap().passed());
+
+		let loose = Comparison::new()
+			.channel_tolerance("x", 0.1 + 1e-12, 0.0)
+			.compare(&golden, &actual);
+		assert!(loose.passed(), "{loose}");
+	}
+
+	#[test]
+	fn fails_on_missing_channels_and_short_runs() {
+		let golden = run(|t| t, 0.25);
+		let mut actual = RunData::new(&["x"]);
+		actual.push(0.0, &[0.0]);
+		actual.push(0.5, &[0.5]);
+
+		let report = Comparison::new().compare(&golden, &actual);
+		assert_eq!(report.missing, ["y"]);
+		assert!(report.channel("x").unwrap().passed());
+		assert!(!report.passed());
+		assert!(report.to_string().contains("this run covers 0 to 0.5"));
+	}
+}
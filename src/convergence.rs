@@ -0,0 +1,276 @@
+//! Step size convergence studies.
+
+use std::{cell::RefCell, collections::BTreeMap, fmt, rc::Rc};
+
+use crate::{Executor, Phase, SimTime};
+
+type Scenario<S> = Box<dyn Fn(f64) -> (Executor<S>, S)>;
+type Accessor<S> = Rc<dyn Fn(&S) -> f64>;
+
+/// Runs the same scenario at successively halved step sizes and estimates how the tracked outputs
+/// converge.
+///
+/// The scenario is built from scratch for every step size, so it works with any integration method
+/// (including multi-rate groups, as long as their step sizes are derived from the `dt` passed in).
+pub struct ConvergenceStudy<S> {
+	dt: f64,
+	levels: usize,
+	tolerance: f64,
+	scenario: Scenario<S>,
+	names: Vec<String>,
+	accessors: Vec<Accessor<S>>,
+}
+
+impl<S: Clone + Default + 'static> ConvergenceStudy<S> {
+	/// Creates a study starting at step size `dt`.
+	///
+	/// `scenario` builds the executor and initial simulation for a given step size.
+	pub fn new<F>(dt: f64, scenario: F) -> Self
+	where
+		F: Fn(f64) -> (Executor<S>, S) + 'static,
+	{
+		Self {
+			dt,
+			levels: 4,
+			tolerance: 1e-6,
+			scenario: Box::new(scenario),
+			names: Vec::new(),
+			accessors: Vec::new(),
+		}
+	}
+
+	/// Sets the number of step sizes to run (`dt`, `dt/2`, `dt/4`, ...), which must be at least 3.
+	pub fn levels(mut self, levels: usize) -> Self {
+		assert!(levels >= 3, "a convergence study needs at least 3 step sizes");
+		self.levels = levels;
+		self
+	}
+
+	/// Sets the largest acceptable estimated truncation error, used to recommend a step size.
+	pub fn tolerance(mut self, tolerance: f64) -> Self {
+		self.tolerance = tolerance;
+		self
+	}
+
+	/// Adds an output to compare between runs.
+	pub fn track<F>(&mut self, name: &str, accessor: F)
+	where
+		F: (Fn(&S) -> f64) + 'static,
+	{
+		self.names.push(name.to_string());
+		self.accessors.push(Rc::new(accessor));
+	}
+
+	pub fn run(&self) -> ConvergenceReport {
+		let step_sizes: Vec<f64> = (0..self.levels)
+			.map(|k| self.dt / f64::powi(2.0, k as i32))
+			.collect();
+
+		// samples of each run, keyed by the step number of the coarsest run
+		let runs: Vec<BTreeMap<u64, Vec<f64>>> = step_sizes
+			.iter()
+			.enumerate()
+			.map(|(k, &dt)| {
+				let samples = self.sample_run(dt);
+				let stride = 1 << k;
+				samples
+					.into_iter()
+					.filter(|(step, _)| step % stride == 0)
+					.map(|(step, values)| (step / stride, values))
+					.collect()
+			})
+			.collect();
+
+		// only compare times that every run reached
+		let common: Vec<u64> = runs[0]
+			.keys()
+			.filter(|step| runs.iter().all(|run| run.contains_key(step)))
+			.copied()
+			.collect();
+
+		let channels: Vec<ChannelConvergence> = self
+			.names
+			.iter()
+			.enumerate()
+			.map(|(c, name)| {
+				let differences: Vec<f64> = runs
+					.windows(2)
+					.map(|pair| {
+						common
+							.iter()
+							.map(|step| (pair[0][step][c] - pair[1][step][c]).abs())
+							.fold(0.0, f64::max)
+					})
+					.collect();
+				ChannelConvergence::new(name, differences)
+			})
+			.collect();
+
+		let recommended_dt = step_sizes.iter().enumerate().find_map(|(k, &dt)| {
+			let error = channels.iter().map(|c| c.errors[k]).fold(0.0, f64::max);
+			(error <= self.tolerance).then_some(dt)
+		});
+
+		ConvergenceReport {
+			step_sizes,
+			channels,
+			tolerance: self.tolerance,
+			recommended_dt,
+		}
+	}
+
+	/// Runs the scenario with step size `dt`, returning the tracked outputs after every step.
+	fn sample_run(&self, dt: f64) -> Vec<(u64, Vec<f64>)> {
+		let (mut exec, sim) = (self.scenario)(dt);
+		let samples = Rc::new(RefCell::new(Vec::new()));
+
+		let sample = |step_offset: u64| {
+			let samples = Rc::clone(&samples);
+			let accessors = self.accessors.clone();
+			move |sim: &mut S, time: &SimTime| {
+				let values = accessors.iter().map(|f| f(sim)).collect();
+				samples.borrow_mut().push((time.step + step_offset, values));
+			}
+		};
+
+		// post-integration jobs run before the step counter advances, but see the state at the end of
+		// the step
+		exec.add_job(Phase::Init, sample(0));
+		exec.add_job(Phase::PostIntegrate, sample(1));
+		exec.run(sim);
+		drop(exec);
+
+		Rc::into_inner(samples).unwrap().into_inner()
+	}
+}
+
+/// Convergence estimates for one output.
+#[derive(Clone, Debug)]
+pub struct ChannelConvergence {
+	pub name: String,
+	/// Largest difference between consecutive step sizes over the common sample times.
+	pub differences: Vec<f64>,
+	/// Empirical order of convergence between consecutive differences.
+	pub orders: Vec<f64>,
+	/// Estimated truncation error at each step size, extrapolated with the finest observed order.
+	pub errors: Vec<f64>,
+}
+
+impl ChannelConvergence {
+	fn new(name: &str, differences: Vec<f64>) -> Self {
+		let orders: Vec<f64> = differences
+			.windows(2)
+			.map(|pair| (pair[0] / pair[1]).log2())
+			.collect();
+
+		// with y(h) = y + C h^p, the difference between h and h/2 is C h^p (1 - 2^-p)
+		let order = orders.last().copied().filter(|p| p.is_finite() && *p > 0.0);
+		let mut errors: Vec<f64> = match order {
+			Some(p) => differences
+				.iter()
+				.map(|d| d / (1.0 - f64::powf(2.0, -p)))
+				.collect(),
+			None => differences.clone(),
+		};
+		let finest = errors.last().copied().unwrap_or(0.0);
+		errors.push(match order {
+			Some(p) => finest / f64::powf(2.0, p),
+			None => finest,
+		});
+
+		Self {
+			name: name.to_string(),
+			differences,
+			orders,
+			errors,
+		}
+	}
+
+	/// Returns the observed order of convergence between the finest step sizes.
+	pub fn order(&self) -> f64 {
+		self.orders.last().copied().unwrap_or(f64::NAN)
+	}
+}
+
+/// The results of a [`ConvergenceStudy`].
+#[derive(Clone, Debug)]
+pub struct ConvergenceReport {
+	pub step_sizes: Vec<f64>,
+	pub channels: Vec<ChannelConvergence>,
+	pub tolerance: f64,
+	/// The largest step size whose estimated error is within the tolerance for every output.
+	pub recommended_dt: Option<f64>,
+}
+
+impl fmt::Display for ConvergenceReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{:<20} {:>8}", "output", "order")?;
+		for dt in &self.step_sizes {
+			write!(f, " {:>12}", format!("err@{dt}"))?;
+		}
+		writeln!(f)?;
+
+		for channel in &self.channels {
+			write!(f, "{:<20} {:>8.3}", channel.name, channel.order())?;
+			for error in &channel.errors {
+				write!(f, " {error:>12.3e}")?;
+			}
+			writeln!(f)?;
+		}
+
+		match self.recommended_dt {
+			Some(dt) => write!(f, "recommended dt for tolerance {}: {dt}", self.tolerance),
+			None => write!(f, "no step size met tolerance {}", self.tolerance),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{IntegrationGroup, Method};
+
+	/// Builds `x' = -x` from `x = 1` for 1 s with the given method and step size.
+	fn decay(method: Method, dt: f64) -> (Executor<f64>, f64) {
+		let mut exec = Executor::new(dt, 1.0);
+		exec.add_integration_group(
+			IntegrationGroup::new("x", |x: &f64, _| vec![*x], |x, _, _| vec![-x], |x, y| *x = y[0])
+				.method(method),
+		);
+		(exec, 1.0)
+	}
+
+	/// Returns the largest error from `e^-t` over a run.
+	fn max_error(method: Method, dt: f64) -> f64 {
+		let (mut exec, x0) = decay(method, dt);
+		let error = Rc::new(RefCell::new(0.0_f64));
+		let max = Rc::clone(&error);
+		exec.add_job(Phase::PostIntegrate, move |x, time| {
+			let t = (time.step + 1) as f64 * time.dt;
+			let mut max = max.borrow_mut();
+			*max = max.max((*x - f64::exp(-t)).abs());
+		});
+		exec.run(x0);
+		*error.borrow()
+	}
+
+	#[test]
+	fn observed_order_matches_the_method() {
+		for (method, order, tolerance) in
+			[(Method::RungeKutta4, 4.0, 1e-9), (Method::Euler, 1.0, 1e-2)]
+		{
+			let mut study = ConvergenceStudy::new(0.1, move |dt| decay(method, dt))
+				.levels(5)
+				.tolerance(tolerance);
+			study.track("x", |x| *x);
+			let report = study.run();
+
+			let observed = report.channels[0].order();
+			assert!((observed - order).abs() < 0.1, "{method:?} converged at {observed}");
+			let dt = report.recommended_dt.unwrap();
+			assert!(dt < 0.1, "{method:?} met the tolerance at the coarsest step");
+			let error = max_error(method, dt);
+			assert!(error <= tolerance, "{method:?} error {error} at dt {dt}");
+		}
+	}
+}
@@ -0,0 +1,249 @@
+//! A log of discrete events, such as phase changes, kept alongside the recorded channels.
+
+use std::cell::RefCell;
+use std::fmt::{self, Write as _};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::recorder::Value;
+
+/// How important an event is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+	Debug,
+	Info,
+	Warning,
+	Error,
+}
+
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Debug => "debug",
+			Self::Info => "info",
+			Self::Warning => "warning",
+			Self::Error => "error",
+		})
+	}
+}
+
+/// Something that happened at one instant of a run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+	/// Simulation time of the state the event was emitted for, which is the end of the frame for
+	/// PostIntegrate jobs and the end of the step for deferred updates.
+	pub t: f64,
+	pub name: String,
+	pub severity: Severity,
+	/// Named values describing the event, such as the velocity at burnout.
+	pub fields: Vec<(String, Value)>,
+}
+
+impl Event {
+	/// Returns the value of a field.
+	pub fn field(&self, key: &str) -> Option<&Value> {
+		self.fields
+			.iter()
+			.find(|(k, _)| k == key)
+			.map(|(_, value)| value)
+	}
+}
+
+#[derive(Default)]
+struct Inner {
+	t: f64,
+	events: Vec<Event>,
+	writer: Option<BufWriter<File>>,
+	error: Option<io::Error>,
+}
+
+/// A handle to an executor's event log, which jobs can keep to emit events.
+///
+/// Get one from [`Executor::event_log`](crate::Executor::event_log). Every clone of the handle
+/// emits into the same log, and events are stamped with the simulation time of the job that is
+/// running. The events of a run are returned in [`RunResult::events`](crate::RunResult::events),
+/// and can also be written to a file as they happen, one JSON object per line.
+#[derive(Clone, Default)]
+pub struct EventLog {
+	inner: Rc<RefCell<Inner>>,
+}
+
+impl EventLog {
+	pub fn emit(&self, severity: Severity, name: &str, fields: &[(&str, Value)]) {
+		let mut inner = self.inner.borrow_mut();
+		let event = Event {
+			t: inner.t,
+			name: name.to_string(),
+			severity,
+			fields: fields
+				.iter()
+				.map(|(key, value)| (key.to_string(), value.clone()))
+				.collect(),
+		};
+		if let Some(writer) = &mut inner.writer
+			&& let Err(e) = writeln!(writer, "{}", to_json(&event))
+		{
+			inner.writer = None;
+			inner.error = Some(e);
+		}
+		inner.events.push(event);
+	}
+
+	pub fn info(&self, name: &str, fields: &[(&str, Value)]) {
+		self.emit(Severity::Info, name, fields);
+	}
+
+	pub fn warning(&self, name: &str, fields: &[(&str, Value)]) {
+		self.emit(Severity::Warning, name, fields);
+	}
+
+	pub fn error(&self, name: &str, fields: &[(&str, Value)]) {
+		self.emit(Severity::Error, name, fields);
+	}
+
+	/// Returns the events emitted so far in the current run.
+	pub fn events(&self) -> Vec<Event> {
+		self.inner.borrow().events.clone()
+	}
+
+	/// Returns the events emitted after the first `start` events of the run.
+	pub(crate) fn events_since(&self, start: usize) -> Vec<Event> {
+		self.inner.borrow().events[start..].to_vec()
+	}
+
+	pub(crate) fn set_time(&self, t: f64) {
+		self.inner.borrow_mut().t = t;
+	}
+
+	/// Clears the log and creates its file, if it has one.
+	pub(crate) fn start(&self, path: Option<&Path>) -> io::Result<()> {
+		let mut inner = self.inner.borrow_mut();
+		inner.events.clear();
+		inner.error = None;
+		inner.writer = match path {
+			Some(path) => Some(BufWriter::new(File::create(path)?)),
+			None => None,
+		};
+		Ok(())
+	}
+
+	/// Flushes the file and returns the events of the run.
+	pub(crate) fn finish(&self) -> io::Result<Vec<Event>> {
+		let mut inner = self.inner.borrow_mut();
+		if let Some(e) = inner.error.take() {
+			return Err(e);
+		}
+		if let Some(mut writer) = inner.writer.take() {
+			writer.flush()?;
+		}
+		Ok(std::mem::take(&mut inner.events))
+	}
+}
+
+/// Formats an event as a JSON object with `t`, `severity`, `name` and its fields as keys.
+fn to_json(event: &Event) -> String {
+	let mut json = format!("{{\"t\":{}", json_number(event.t));
+	write!(json, ",\"severity\":\"{}\"", event.severity).unwrap();
+	write!(json, ",\"name\":{}", json_string(&event.name)).unwrap();
+	for (key, value) in &event.fields {
+		let value = match value {
+			Value::F64(x) => json_number(*x),
+			Value::Bool(b) => b.to_string(),
+			Value::Int(i) => i.to_string(),
+			Value::Uint(u) => u.to_string(),
+			Value::Enum(index) => index.to_string(),
+			Value::Str(s) => json_string(s),
+		};
+		write!(json, ",{}:{value}", json_string(key)).unwrap();
+	}
+	json.push('}');
+	json
+}
+
+/// Formats a number for JSON, which has no NaN or infinity, so they are written as `null`.
+fn json_number(x: f64) -> String {
+	if x.is_finite() {
+		x.to_string()
+	} else {
+		"null".to_string()
+	}
+}
+
+fn json_string(s: &str) -> String {
+	let mut json = String::from("\"");
+	for c in s.chars() {
+		match c {
+			'"' => json.push_str("\\\""),
+			'\\' => json.push_str("\\\\"),
+			'\n' => json.push_str("\\n"),
+			'\r' => json.push_str("\\r"),
+			'\t' => json.push_str("\\t"),
+			c if c.is_control() => write!(json, "\\u{:04x}", c as u32).unwrap(),
+			c => json.push(c),
+		}
+	}
+	json.push('"');
+	json
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn events_are_written_as_json_lines() {
+		let path = std::env::temp_dir().join("simlib-events.jsonl");
+		let log = EventLog::default();
+		log.start(Some(&path)).unwrap();
+		log.set_time(1.5);
+		log.info("Burnout", &[("velocity", Value::F64(120.5)), ("stage", Value::Uint(1))]);
+		log.clone()
+			.warning("Note \"x\"", &[("text", Value::Str("a\nb".into()))]);
+
+		let events = log.finish().unwrap();
+		assert_eq!(events.len(), 2);
+		assert_eq!(events[0].field("velocity"), Some(&Value::F64(120.5)));
+		assert_eq!(events[1].severity, Severity::Warning);
+		assert_eq!(
+			std::fs::read_to_string(&path).unwrap(),
+			concat!(
+				"{\"t\":1.5,\"severity\":\"info\",\"name\":\"Burnout\",\"velocity\":120.5,",
+				"\"stage\":1}\n",
+				"{\"t\":1.5,\"severity\":\"warning\",\"name\":\"Note \\\"x\\\"\",",
+				"\"text\":\"a\\nb\"}\n",
+			)
+		);
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn events_are_stamped_with_simulation_time() {
+		let mut exec = crate::Executor::<f64>::new(0.5, 2.0);
+		let log = exec.event_log();
+		exec.set_integrator(
+			|x, _| vec![*x],
+			move |_, time, updates| {
+				if time.step == 1 && updates.is_empty() {
+					let log = log.clone();
+					updates.defer(move |_| log.info("Deferred", &[]));
+				}
+				vec![1.0]
+			},
+			|x, y| *x = y[0],
+		);
+		let log = exec.event_log();
+		exec.add_job(crate::Phase::PostIntegrate, move |x, time| {
+			if time.step == 2 {
+				log.warning("Frame", &[("x", Value::F64(*x))]);
+			}
+		});
+
+		let events = exec.run(0.0).events;
+		let stamps: Vec<_> = events.iter().map(|e| (e.name.as_str(), e.t)).collect();
+		assert_eq!(stamps, [("Deferred", 1.0), ("Frame", 1.5)]);
+		assert_eq!(events[1].field("x"), Some(&Value::F64(1.5)));
+		assert!(exec.event_log().events().is_empty());
+	}
+}
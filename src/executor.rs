@@ -1,8 +1,17 @@
-use std::collections::HashMap;
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+};
 
 use crate::{
-	integrator::{Integrator, runge_kutta_4},
-	recorder::Recorder,
+	events::{Event, EventLog, Severity},
+	health::{self, NonFinite, NonFiniteAction, NonFiniteSource},
+	integrator::{self, Coupling, Deferred, IntegrationGroup, IntegratorStats},
+	linalg::Matrix,
+	linearize::{self, Inputs, Linearization, Trim, TrimError, TrimResult},
+	metrics::{Metric, Reduction, Summary},
+	recorder::{Recorder, SamplePoint, Value},
+	run_data::RunData,
 };
 
 #[derive(Clone, Copy, Debug)]
@@ -20,15 +29,47 @@ pub enum Phase {
 	Shutdown,
 }
 
+/// How a run ended.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RunStatus {
+	/// The run reached its end time.
+	Completed,
+	/// The run was stopped because integration produced a non-finite value.
+	NonFinite(NonFinite),
+}
+
+/// The outcome of [`Executor::run`].
+#[derive(Clone, Debug)]
+pub struct RunResult {
+	pub status: RunStatus,
+	/// Simulation time when the run ended.
+	pub time: SimTime,
+	/// Statistics for each integration group, in registration order.
+	pub integrator_stats: Vec<IntegratorStats>,
+	/// Samples of each recorder that keeps them in memory, in the order the recorders were added.
+	pub recordings: Vec<RunData>,
+	/// Events emitted during the run, in the order they were emitted.
+	pub events: Vec<Event>,
+	/// The value of each metric, in the order the metrics were added.
+	pub summary: Summary,
+}
+
 type Job<S> = Box<dyn FnMut(&mut S, &SimTime)>;
 
 pub struct Executor<S> {
 	time: SimTime,
 	end_time: f64,
 	jobs: HashMap<Phase, Vec<Job<S>>>,
-	integrator: Option<Integrator<S>>,
+	groups: Vec<IntegrationGroup<S>>,
 	last_state: S,
-	recorder: Option<Recorder<S>>,
+	recorders: Vec<Recorder<S>>,
+	non_finite_action: NonFiniteAction,
+	warned_non_finite: bool,
+	events: EventLog,
+	event_log_path: Option<PathBuf>,
+	metrics: Vec<Metric<S>>,
+	// number of events the metrics have seen this run
+	metric_events: usize,
 }
 
 impl<S: Clone + Default> Executor<S> {
@@ -41,27 +82,148 @@ pub fn new(dt: f64, end_time: f64) -> Self {
 			},
 			end_time,
 			jobs: HashMap::new(),
-			integrator: None,
+			groups: Vec::new(),
 			last_state: S::default(),
-			recorder: None,
+			recorders: Vec::new(),
+			non_finite_action: NonFiniteAction::default(),
+			warned_non_finite: false,
+			events: EventLog::default(),
+			event_log_path: None,
+			metrics: Vec::new(),
+			metric_events: 0,
 		}
 	}
 
-	pub fn set_recorder(&mut self, recorder: Recorder<S>) {
-		self.recorder = Some(recorder);
+	/// Adds a recorder. Each recorder samples at its own rate and writes its own file.
+	pub fn add_recorder(&mut self, recorder: Recorder<S>) {
+		self.recorders.push(recorder);
+	}
+
+	/// Returns a handle to the event log, for jobs and derivatives to emit events.
+	///
+	/// Events emitted by a job are stamped with the time of the state it sees, which is the end of
+	/// the frame for PostIntegrate jobs, and events emitted by a deferred update with the time of
+	/// the step that applied it.
+	pub fn event_log(&self) -> EventLog {
+		self.events.clone()
+	}
+
+	/// Writes the events of each run to `path` as they are emitted, one JSON object per line.
+	pub fn set_event_log_file<P: AsRef<Path>>(&mut self, path: P) {
+		self.event_log_path = Some(path.as_ref().to_path_buf());
+	}
+
+	/// Adds a metric that reduces `accessor` over each run, for the run's [`Summary`].
+	pub fn add_metric<F>(&mut self, name: &str, reduction: Reduction, accessor: F) -> &mut Metric<S>
+	where
+		F: Fn(&S) -> f64 + 'static,
+	{
+		self.metrics.push(Metric::new(name, reduction, accessor));
+		self.metrics.last_mut().unwrap()
+	}
+
+	/// Sets what happens when integration produces a NaN or infinite value.
+	///
+	/// Derivatives are checked after every evaluation and states after every integration step.
+	pub fn set_non_finite_action(&mut self, action: NonFiniteAction) {
+		self.non_finite_action = action;
 	}
 
+	/// Sets a single integration group that is stepped with RK4 at the frame rate, replacing any
+	/// existing groups.
 	pub fn set_integrator<L, D, U>(&mut self, state_loader: L, derivative: D, state_unloader: U)
 	where
-		L: Fn(&mut S, &SimTime) -> Vec<f64> + 'static,
-		D: Fn(&mut S, &SimTime) -> Vec<f64> + 'static,
+		L: Fn(&S, &SimTime) -> Vec<f64> + 'static,
+		D: Fn(&S, &SimTime, &mut Deferred<S>) -> Vec<f64> + 'static,
 		U: FnMut(&mut S, &[f64]) + 'static,
 	{
-		self.integrator = Some(Integrator {
-			state_loader: Box::new(state_loader),
-			derivative: Box::new(derivative),
-			state_unloader: Box::new(state_unloader),
-		});
+		self.groups.clear();
+		self.add_integration_group(IntegrationGroup::new(
+			"default",
+			state_loader,
+			derivative,
+			state_unloader,
+		));
+	}
+
+	/// Adds a group of states that is integrated with its own step size and method.
+	///
+	/// # Panics
+	///
+	/// Panics if the frame `dt` is not a whole multiple of the group's step size, or if one of the
+	/// group's named states is already owned by another group.
+	pub fn add_integration_group(&mut self, group: IntegrationGroup<S>) {
+		if let Some(dt) = group.dt {
+			let substeps = (self.time.dt / dt).round();
+			assert!(
+				substeps >= 1.0 && (substeps * dt - self.time.dt).abs() <= 1e-9 * self.time.dt,
+				"step size {dt} of group `{}` does not evenly divide the frame dt {}",
+				group.name,
+				self.time.dt
+			);
+		}
+
+		for state in &group.states {
+			if let Some(owner) = self.state_owner(state) {
+				panic!(
+					"state `{state}` of group `{}` is already owned by group `{owner}`",
+					group.name
+				);
+			}
+		}
+
+		self.groups.push(group);
+	}
+
+	/// Returns the name of the integration group that owns the named state.
+	pub fn state_owner(&self, state: &str) -> Option<&str> {
+		self.groups
+			.iter()
+			.find(|group| group.states.iter().any(|name| name == state))
+			.map(|group| group.name.as_str())
+	}
+
+	/// Linearizes the named integration group about the states and inputs in `sim`, at the current
+	/// simulation time.
+	///
+	/// # Panics
+	///
+	/// Panics if there is no integration group with the given name.
+	pub fn linearize(&mut self, group: &str, sim: &S, inputs: &mut Inputs<S>) -> Linearization {
+		let time = self.time;
+		linearize::linearize(self.group_mut(group), inputs, sim, &time)
+	}
+
+	/// Finds a trim point of the named integration group with Newton's method, starting from the
+	/// states and inputs in `sim`.
+	///
+	/// # Panics
+	///
+	/// Panics if there is no integration group with the given name.
+	pub fn trim(
+		&mut self,
+		group: &str,
+		sim: &S,
+		inputs: &mut Inputs<S>,
+		settings: &Trim,
+	) -> Result<TrimResult<S>, TrimError> {
+		let time = self.time;
+		linearize::trim(self.group_mut(group), inputs, sim, &time, settings)
+	}
+
+	/// Returns the statistics of each integration group for the current run.
+	pub fn integrator_stats(&self) -> Vec<IntegratorStats> {
+		self.groups
+			.iter()
+			.map(|group| group.stats.clone())
+			.collect()
+	}
+
+	fn group_mut(&mut self, name: &str) -> &mut IntegrationGroup<S> {
+		self.groups
+			.iter_mut()
+			.find(|group| group.name == name)
+			.unwrap_or_else(|| panic!("no integration group named `{name}`"))
 	}
 
 	pub fn add_job<F>(&mut self, phase: Phase, job: F)
@@ -71,30 +233,48 @@ pub fn add_job<F>(&mut self, phase: Phase, job: F)
 		self.jobs.entry(phase).or_default().push(Box::new(job));
 	}
 
-	pub fn run(&mut self, mut sim: S) {
+	pub fn run(&mut self, mut sim: S) -> RunResult {
+		let mut status = RunStatus::Completed;
+		self.time = SimTime {
+			t: 0.0,
+			step: 0,
+			..self.time
+		};
+		self.warned_non_finite = false;
+		for group in &mut self.groups {
+			group.reset();
+		}
+		for recorder in &mut self.recorders {
+			recorder.start().unwrap();
+		}
+		self.events.start(self.event_log_path.as_deref()).unwrap();
+		for metric in &mut self.metrics {
+			metric.reset();
+		}
+		self.metric_events = 0;
+
 		self.run_phase(Phase::Init, &mut sim);
 		self.last_state = sim.clone();
 
+		for recorder in &mut self.recorders {
+			let stats = self.groups.iter().map(|group| &group.stats);
+			recorder.sample_initial(&sim, self.time.t, stats).unwrap();
+		}
+		self.update_metrics(&sim);
+
 		while self.time.t < self.end_time {
+			self.sample(SamplePoint::FrameStart, &sim, self.time.t);
+
 			self.run_phase(Phase::PreIntegrate, &mut sim);
 
-			if let Some(integrator) = &mut self.integrator {
-				let Integrator {
-					state_loader,
-					derivative,
-					state_unloader,
-				} = integrator;
-
-				runge_kutta_4(
-					&mut sim,
-					state_loader,
-					derivative,
-					state_unloader,
-					self.time.dt,
-					&self.time,
-				);
+			if let Some(non_finite) = self.integrate(&mut sim) {
+				status = RunStatus::NonFinite(non_finite);
+				break;
 			}
 
+			let end_of_frame = self.time.dt * (self.time.step + 1) as f64;
+			self.sample(SamplePoint::AfterIntegration, &sim, end_of_frame);
+
 			self.run_phase(Phase::PostIntegrate, &mut sim);
 
 			self.time.step += 1;
@@ -103,23 +283,467 @@ pub fn run(&mut self, mut sim: S) {
 			// checkpoint
 			self.last_state = sim.clone();
 
-			if let Some(recorder) = &mut self.recorder {
-				recorder.sample(&sim, self.time.t);
-			}
+			self.sample(SamplePoint::FrameEnd, &sim, self.time.t);
+			self.update_metrics(&sim);
 		}
 
 		self.run_phase(Phase::Shutdown, &mut sim);
 
-		if let Some(recorder) = &self.recorder {
-			recorder.write_csv().unwrap();
+		for recorder in &mut self.recorders {
+			let stats = self.groups.iter().map(|group| &group.stats);
+			recorder.sample_final(&sim, self.time.t, stats).unwrap();
+			recorder.finish().unwrap();
+		}
+		let events = self.events.finish().unwrap();
+
+		RunResult {
+			status,
+			time: self.time,
+			integrator_stats: self.integrator_stats(),
+			recordings: self
+				.recorders
+				.iter_mut()
+				.filter_map(Recorder::take_data)
+				.collect(),
+			events,
+			summary: Summary {
+				metrics: self.metrics.iter().map(Metric::value).collect(),
+			},
+		}
+	}
+
+	fn update_metrics(&mut self, sim: &S) {
+		if self.metrics.is_empty() {
+			return;
+		}
+		let events = self.events.events_since(self.metric_events);
+		self.metric_events += events.len();
+		for metric in &mut self.metrics {
+			metric.update(sim, self.time.t, &events);
+		}
+	}
+
+	/// Samples the recorders that sample at `point`.
+	fn sample(&mut self, point: SamplePoint, sim: &S, t: f64) {
+		for recorder in &mut self.recorders {
+			let stats = self.groups.iter().map(|group| &group.stats);
+			recorder.sample_at(point, sim, t, stats).unwrap();
 		}
 	}
 
 	fn run_phase(&mut self, phase: Phase, sim: &mut S) {
+		// PostIntegrate jobs see the state at the end of the frame, before the time is advanced
+		if phase == Phase::PostIntegrate {
+			self.events
+				.set_time(self.time.dt * (self.time.step + 1) as f64);
+		} else {
+			self.events.set_time(self.time.t);
+		}
 		if let Some(jobs) = self.jobs.get_mut(&phase) {
 			for job in jobs {
 				job(sim, &self.time);
 			}
 		}
 	}
+
+	/// Advances every integration group through one frame.
+	///
+	/// Returns a diagnostic if a non-finite value was found and the run should stop.
+	fn integrate(&mut self, sim: &mut S) -> Option<NonFinite> {
+		if self.groups.is_empty() {
+			return None;
+		}
+
+		let frame = self.time;
+
+		// slower groups are advanced first so that faster groups can be coupled to their end-of-frame
+		// values; the sort is stable, so groups with equal rates keep their registration order
+		let mut order: Vec<usize> = (0..self.groups.len()).collect();
+		order.sort_by(|&a, &b| {
+			let dt_a = self.groups[a].dt.unwrap_or(frame.dt);
+			let dt_b = self.groups[b].dt.unwrap_or(frame.dt);
+			dt_b.total_cmp(&dt_a)
+		});
+
+		let starts: Vec<Vec<f64>> = self
+			.groups
+			.iter()
+			.map(|group| {
+				let start = (group.state_loader)(sim, &frame);
+				assert!(
+					group.states.is_empty() || group.states.len() == start.len(),
+					"group `{}` names {} states but its state loader returned {}",
+					group.name,
+					group.states.len(),
+					start.len()
+				);
+				start
+			})
+			.collect();
+		let mut ends: Vec<Option<Vec<f64>>> = vec![None; self.groups.len()];
+
+		for i in order {
+			let (before, rest) = self.groups.split_at_mut(i);
+			let (group, after) = rest.split_first_mut().unwrap();
+
+			let h = group.dt.unwrap_or(frame.dt);
+			let substeps = (frame.dt / h).round() as u64;
+
+			let IntegrationGroup {
+				name,
+				states,
+				method,
+				coupling,
+				state_loader,
+				derivative,
+				state_unloader,
+				history,
+				variational,
+				stats,
+				..
+			} = group;
+
+			// sets the states of the groups that were already advanced this frame to the values seen
+			// by this group at time `t`
+			let mut couple = |sim: &mut S, t: f64| {
+				let others = before.iter_mut().chain(after.iter_mut());
+				let indices = (0..i).chain(i + 1..);
+
+				for (j, other) in indices.zip(others) {
+					let Some(end) = &ends[j] else {
+						continue;
+					};
+
+					match coupling {
+						Coupling::ZeroOrderHold => (other.state_unloader)(sim, &starts[j]),
+						Coupling::Interpolate => {
+							let alpha = (t - frame.t) / frame.dt;
+							let values: Vec<f64> = starts[j]
+								.iter()
+								.zip(end)
+								.map(|(y0, y1)| y0 + alpha * (y1 - y0))
+								.collect();
+							(other.state_unloader)(sim, &values);
+						},
+					}
+				}
+			};
+
+			let mut y = starts[i].clone();
+			let n = y.len();
+
+			// the state transition matrix is integrated as extra elements after the state
+			if let Some(variational) = variational {
+				let psi = match variational.psi.take() {
+					Some(psi) => psi,
+					None => variational.initial_psi(sim, &frame, n),
+				};
+				y.extend_from_slice(psi.as_slice());
+			}
+
+			for k in 0..substeps {
+				let time = SimTime {
+					t: frame.t + k as f64 * h,
+					..frame
+				};
+
+				let mut deferred = Deferred::new();
+				// collects the updates requested at trial states, which are thrown away
+				let mut scratch = Deferred::new();
+				let mut non_finite = None;
+				let mut evaluation = 0;
+
+				// describes a non-finite value found while taking this step
+				let diagnose = |source, index, value, t| NonFinite {
+					group: name.clone(),
+					source,
+					index,
+					state: states.get(index).cloned(),
+					value,
+					t,
+					step: frame.step,
+					substep: k,
+					last_good_state: y[..n].to_vec(),
+				};
+
+				let mut f = |z: &[f64], time: &SimTime, accepted: bool| {
+					let (y, psi) = z.split_at(n);
+					state_unloader(sim, y);
+					couple(sim, time.t);
+					let mut dz = if accepted {
+						derivative(sim, time, &mut deferred)
+					} else {
+						scratch = Deferred::new();
+						derivative(sim, time, &mut scratch)
+					};
+
+					evaluation += 1;
+					stats.derivative_calls += 1;
+					if non_finite.is_none()
+						&& let Some((index, value)) = health::find_non_finite(&dz)
+					{
+						let source = NonFiniteSource::Derivative { evaluation };
+						non_finite = Some(diagnose(source, index, value, time.t));
+					}
+
+					if let Some(variational) = variational.as_mut() {
+						let d_psi =
+							variational.derivative(sim, y, psi, time, derivative, state_unloader);
+						stats.jacobian_evaluations += 1;
+						stats.derivative_calls += variational.jacobian_cost(d_psi.len() / n.max(1));
+						dz.extend(d_psi);
+					}
+
+					dz
+				};
+				let next = integrator::step(*method, &mut f, &y, &time, h, history);
+
+				if non_finite.is_none()
+					&& let Some((index, value)) = health::find_non_finite(&next[..n])
+				{
+					non_finite = Some(diagnose(NonFiniteSource::State, index, value, time.t + h));
+				}
+
+				if let Some(non_finite) = non_finite {
+					match self.non_finite_action {
+						NonFiniteAction::Warn => {
+							if !self.warned_non_finite {
+								eprintln!("warning: {non_finite}");
+								log_non_finite(&self.events, Severity::Warning, &non_finite);
+								self.warned_non_finite = true;
+							}
+						},
+						NonFiniteAction::Stop => {
+							// the run ends at the start of the frame, so every group goes back to
+							// its state from then, including those already advanced
+							state_unloader(sim, &starts[i]);
+							let others = before.iter_mut().chain(after.iter_mut());
+							for (j, other) in (0..i).chain(i + 1..).zip(others) {
+								(other.state_unloader)(sim, &starts[j]);
+							}
+							log_non_finite(&self.events, Severity::Error, &non_finite);
+							return Some(non_finite);
+						},
+						NonFiniteAction::Panic => panic!("{non_finite}"),
+					}
+				}
+				y = next;
+				stats.record_step(h);
+
+				// the step is accepted, so discrete updates requested while taking it can be applied
+				state_unloader(sim, &y[..n]);
+				self.events.set_time(time.t + h);
+				if deferred.commit(sim) {
+					// the update may have changed the continuous state or made the derivative
+					// discontinuous, so any derivative history is no longer valid
+					history.clear();
+					let t_end = SimTime {
+						t: time.t + h,
+						..time
+					};
+					y[..n].copy_from_slice(&state_loader(sim, &t_end));
+				}
+			}
+
+			let psi = y.split_off(n);
+			state_unloader(sim, &y);
+			if let Some(variational) = variational {
+				let psi = Matrix::from_row_major(n, psi.len() / n.max(1), psi);
+				(variational.unloader)(sim, &psi);
+				variational.psi = Some(psi);
+			}
+			ends[i] = Some(y);
+
+			// put the other advanced groups back at their end-of-frame values
+			for (j, other) in self.groups.iter_mut().enumerate() {
+				if j != i
+					&& let Some(end) = &ends[j]
+				{
+					(other.state_unloader)(sim, end);
+				}
+			}
+		}
+
+		None
+	}
+}
+
+/// Emits a `NonFinite` event naming the group and state where a non-finite value was found.
+fn log_non_finite(events: &EventLog, severity: Severity, non_finite: &NonFinite) {
+	events.set_time(non_finite.t);
+	let mut fields = vec![("group", Value::Str(non_finite.group.clone()))];
+	if let Some(state) = &non_finite.state {
+		fields.push(("state", Value::Str(state.clone())));
+	}
+	fields.push(("value", Value::F64(non_finite.value)));
+	events.emit(severity, "NonFinite", &fields);
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cell::RefCell;
+	use std::rc::Rc;
+
+	use super::*;
+	use crate::Method;
+
+	/// An executor for `x' = 1` whose derivative turns NaN from t = 1.
+	fn failing_executor(action: NonFiniteAction) -> Executor<f64> {
+		let mut exec = Executor::new(0.5, 2.0);
+		exec.set_integrator(
+			|x, _| vec![*x],
+			|_, time, _| vec![if time.t >= 1.0 { f64::NAN } else { 1.0 }],
+			|x, y| *x = y[0],
+		);
+		exec.set_non_finite_action(action);
+		exec
+	}
+
+	#[test]
+	fn non_finite_warn_keeps_running_and_warns_every_run() {
+		let mut exec = failing_executor(NonFiniteAction::Warn);
+		for _ in 0..2 {
+			let result = exec.run(0.0);
+			assert_eq!(result.status, RunStatus::Completed);
+			assert_eq!(result.time.t, 2.0);
+			let warnings: Vec<_> = result.events.iter().map(|e| (e.severity, e.t)).collect();
+			assert_eq!(warnings, [(Severity::Warning, 1.0)]);
+		}
+	}
+
+	#[test]
+	fn non_finite_stop_ends_the_run_at_the_last_good_state() {
+		let mut exec = failing_executor(NonFiniteAction::Stop);
+		let mut recorder = Recorder::in_memory();
+		recorder.track("x", |x| *x);
+		recorder.set_final_sample(true);
+		exec.add_recorder(recorder);
+
+		let result = exec.run(0.0);
+		let RunStatus::NonFinite(non_finite) = &result.status else {
+			panic!("run completed");
+		};
+		// the last stage of the step from 0.5 s evaluates the derivative at 1 s
+		assert_eq!((non_finite.t, non_finite.step), (1.0, 1));
+		assert_eq!(non_finite.last_good_state, [0.5]);
+		assert_eq!(result.time.t, 0.5);
+		assert_eq!(result.events[0].severity, Severity::Error);
+		assert_eq!(result.recordings[0].column("x").unwrap().last(), Some(&0.5));
+	}
+
+	#[test]
+	fn non_finite_stop_puts_every_group_back_at_the_start_of_the_frame() {
+		// `slow` is advanced to 2 first, then `fast` fails part way through the frame from 1 s, so
+		// both go back to their values at 1 s
+		let mut exec = Executor::<(f64, f64)>::new(1.0, 3.0);
+		exec.add_integration_group(IntegrationGroup::new(
+			"slow",
+			|s: &(f64, f64), _| vec![s.0],
+			|_, _, _| vec![1.0],
+			|s, y| s.0 = y[0],
+		));
+		exec.add_integration_group(
+			IntegrationGroup::new(
+				"fast",
+				|s: &(f64, f64), _| vec![s.1],
+				|_, time, _| vec![if time.t >= 1.5 { f64::NAN } else { 1.0 }],
+				|s, y| s.1 = y[0],
+			)
+			.dt(0.5),
+		);
+		exec.set_non_finite_action(NonFiniteAction::Stop);
+		let mut recorder = Recorder::in_memory();
+		recorder.track("slow", |s: &(f64, f64)| s.0);
+		recorder.track("fast", |s| s.1);
+		recorder.set_final_sample(true);
+		exec.add_recorder(recorder);
+
+		let result = exec.run((0.0, 0.0));
+		let RunStatus::NonFinite(non_finite) = &result.status else {
+			panic!("run completed");
+		};
+		assert_eq!((non_finite.substep, non_finite.last_good_state.as_slice()), (0, &[1.0][..]));
+		assert_eq!(result.time.t, 1.0);
+		let data = &result.recordings[0];
+		assert_eq!(data.time().last(), Some(&1.0));
+		assert_eq!(data.column("slow").unwrap().last(), Some(&1.0));
+		assert_eq!(data.column("fast").unwrap().last(), Some(&1.0));
+	}
+
+	/// Runs a slow group `a' = 1` with 1 s steps and a fast group `b' = a` with 0.25 s RK4 substeps
+	/// for 4 s, returning the recorded `a` and `b` at the end of each frame.
+	fn coupled_run(coupling: Coupling) -> (Vec<f64>, Vec<f64>) {
+		let mut exec = Executor::<(f64, f64)>::new(1.0, 4.0);
+		exec.add_integration_group(IntegrationGroup::new(
+			"a",
+			|s: &(f64, f64), _| vec![s.0],
+			|_, _, _| vec![1.0],
+			|s, y| s.0 = y[0],
+		));
+		exec.add_integration_group(
+			IntegrationGroup::new(
+				"b",
+				|s: &(f64, f64), _| vec![s.1],
+				|s, _, _| vec![s.0],
+				|s, y| s.1 = y[0],
+			)
+			.dt(0.25)
+			.coupling(coupling),
+		);
+		let mut recorder = Recorder::in_memory();
+		recorder.track("a", |s: &(f64, f64)| s.0);
+		recorder.track("b", |s| s.1);
+		exec.add_recorder(recorder);
+
+		let result = exec.run((0.0, 0.0));
+		assert_eq!(result.integrator_stats[0].accepted_steps, 4);
+		assert_eq!(result.integrator_stats[1].accepted_steps, 16);
+		let data = &result.recordings[0];
+		(data.column("a").unwrap().to_vec(), data.column("b").unwrap().to_vec())
+	}
+
+	#[test]
+	fn fast_groups_are_coupled_to_slow_groups_within_the_frame() {
+		// held at its start-of-frame value k, `a` adds k to `b` over the frame from k
+		let (a, b) = coupled_run(Coupling::ZeroOrderHold);
+		assert_eq!(a, [0.0, 1.0, 2.0, 3.0, 4.0]);
+		assert_eq!(b, [0.0, 0.0, 1.0, 3.0, 6.0]);
+
+		// interpolated, `a` is exactly t, so `b` is t^2 / 2, which RK4 integrates exactly
+		let (a, b) = coupled_run(Coupling::Interpolate);
+		assert_eq!(a, [0.0, 1.0, 2.0, 3.0, 4.0]);
+		assert_eq!(b, [0.0, 0.5, 2.0, 4.5, 8.0]);
+	}
+
+	#[test]
+	fn substeps_see_their_own_times_and_step_size() {
+		let times = Rc::new(RefCell::new(Vec::new()));
+		let seen = Rc::clone(&times);
+		let mut exec = Executor::<f64>::new(1.0, 2.0);
+		exec.add_integration_group(
+			IntegrationGroup::new(
+				"x",
+				|x: &f64, _| vec![*x],
+				move |_, time, _| {
+					seen.borrow_mut().push((time.t, time.dt, time.step));
+					vec![1.0]
+				},
+				|x, y| *x = y[0],
+			)
+			.dt(0.25)
+			.method(Method::Euler),
+		);
+
+		let result = exec.run(0.0);
+		assert_eq!(result.integrator_stats[0].accepted_steps, 8);
+		assert_eq!(result.integrator_stats[0].integrated_time, 2.0);
+		let expected: Vec<_> = (0..8).map(|k| (k as f64 * 0.25, 0.25, k / 4)).collect();
+		assert_eq!(*times.borrow(), expected);
+	}
+
+	#[test]
+	#[should_panic(expected = "non-finite derivative of state 0 in group `default`")]
+	fn non_finite_panic_panics() {
+		failing_executor(NonFiniteAction::Panic).run(0.0);
+	}
 }
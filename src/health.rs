@@ -0,0 +1,76 @@
+//! Detection of non-finite values during integration.
+
+use std::fmt;
+
+/// What the executor does when integration produces a NaN or infinite value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonFiniteAction {
+	/// Print a diagnostic the first time it happens and keep running.
+	#[default]
+	Warn,
+	/// Put every group back at its state from the start of the frame, then stop the run there as if
+	/// it had reached its end time (shutdown jobs still run and recordings are still written).
+	Stop,
+	/// Panic with a diagnostic.
+	Panic,
+}
+
+/// Where a non-finite value was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonFiniteSource {
+	/// The derivative returned a non-finite value on the given evaluation (starting at 1) within an
+	/// integration step.
+	Derivative { evaluation: usize },
+	/// The integrated state at the end of a step was non-finite.
+	State,
+}
+
+/// A diagnostic describing the first non-finite value produced by an integration step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NonFinite {
+	pub group: String,
+	pub source: NonFiniteSource,
+	/// Index of the offending value in the group's state vector.
+	pub index: usize,
+	/// Name of the offending state, if the group names its states.
+	pub state: Option<String>,
+	pub value: f64,
+	/// Time at which the offending value was evaluated.
+	pub t: f64,
+	pub step: u64,
+	/// Index of the group's integration step within the frame.
+	pub substep: u64,
+	/// State of the group at the start of the step, which was the last good state.
+	pub last_good_state: Vec<f64>,
+}
+
+impl fmt::Display for NonFinite {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let what = match self.source {
+			NonFiniteSource::Derivative { .. } => "derivative of state",
+			NonFiniteSource::State => "state",
+		};
+		write!(f, "non-finite {what} ")?;
+		match &self.state {
+			Some(name) => write!(f, "`{name}` (index {})", self.index)?,
+			None => write!(f, "{}", self.index)?,
+		}
+		write!(
+			f,
+			" in group `{}`: {} at t={} (step {}, substep {}",
+			self.group, self.value, self.t, self.step, self.substep
+		)?;
+		if let NonFiniteSource::Derivative { evaluation } = self.source {
+			write!(f, ", evaluation {evaluation}")?;
+		}
+		write!(f, "); last good state: {:?}", self.last_good_state)
+	}
+}
+
+/// Returns the index and value of the first non-finite element of `values`.
+pub(crate) fn find_non_finite(values: &[f64]) -> Option<(usize, f64)> {
+	values
+		.iter()
+		.position(|x| !x.is_finite())
+		.map(|index| (index, values[index]))
+}
@@ -1,5 +1,654 @@
-use crate::SimTime;
+use std::{collections::VecDeque, fmt};
 
+use crate::{
+	SimTime,
+	linalg::{Matrix, central_difference_jacobian},
+};
+
+pub(crate) type StateLoader<S> = Box<dyn Fn(&S, &SimTime) -> Vec<f64>>;
+pub(crate) type Derivative<S> = Box<dyn Fn(&S, &SimTime, &mut Deferred<S>) -> Vec<f64>>;
+pub(crate) type StateUnloader<S> = Box<dyn FnMut(&mut S, &[f64])>;
+
+type Update<S> = Box<dyn FnOnce(&mut S)>;
+type JacobianFn<S> = Box<dyn Fn(&S, &SimTime) -> Matrix>;
+type StmUnloader<S> = Box<dyn FnMut(&mut S, &Matrix)>;
+
+/// Discrete state updates requested while evaluating a derivative.
+///
+/// Derivatives only get read access to the simulation, because they are evaluated at trial states
+/// that the integrator may throw away (for example the intermediate stages of RK4). Discrete changes
+/// such as a flight phase switch are requested here instead, and are applied in the order they were
+/// requested once the step that requested them is accepted.
+///
+/// Only updates requested at accepted states are kept: the state at the start of the step for
+/// Euler and RK4, and the corrected state at the end of the step for Adams-Bashforth-Moulton (or
+/// the start, when its history restarts). Updates requested at trial states are thrown away with
+/// them, so each update is applied once per request rather than once per stage.
+pub struct Deferred<S> {
+	updates: Vec<Update<S>>,
+}
+
+impl<S> Deferred<S> {
+	pub(crate) fn new() -> Self {
+		Self {
+			updates: Vec::new(),
+		}
+	}
+
+	/// Requests an update that is applied to the simulation when the current step is accepted.
+	pub fn defer<F>(&mut self, update: F)
+	where
+		F: FnOnce(&mut S) + 'static,
+	{
+		self.updates.push(Box::new(update));
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.updates.is_empty()
+	}
+
+	/// Applies every requested update to `sim`, returning true if there were any.
+	pub(crate) fn commit(&mut self, sim: &mut S) -> bool {
+		let committed = !self.updates.is_empty();
+		for update in self.updates.drain(..) {
+			update(sim);
+		}
+		committed
+	}
+}
+
+/// A numerical integration method.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Method {
+	/// Forward Euler (first order, one derivative evaluation per step).
+	Euler,
+	/// Classic fourth-order Runge-Kutta (four derivative evaluations per step).
+	#[default]
+	RungeKutta4,
+	/// Adams-Bashforth-Moulton predictor-corrector of the given order (1 to 5), run in PECE mode
+	/// (two derivative evaluations per step).
+	///
+	/// The method keeps a history of past derivatives, which is started with RK4 steps. The history
+	/// is restarted whenever the state handed to the integrator is not the state it produced on the
+	/// previous step (for example after a job resets a state), or when the step size changes. States
+	/// are compared to within rounding, so converting them through the simulation's own units does
+	/// not restart the history.
+	AdamsBashforthMoulton(usize),
+}
+
+/// How an integration group sees states owned by groups that were already advanced in the current
+/// frame.
+///
+/// Groups are advanced once per frame in order of decreasing step size, so a fast group always runs
+/// after the slower groups it depends on. Slower groups always see the states of faster groups held
+/// at their values from the start of the frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Coupling {
+	/// Hold the other groups' states at their values from the start of the frame.
+	#[default]
+	ZeroOrderHold,
+	/// Linearly interpolate the other groups' states between their values at the start and end of
+	/// the frame.
+	Interpolate,
+}
+
+/// How the Jacobian of a group's derivative is computed for propagating its state transition matrix.
+pub enum Jacobian<S> {
+	/// Central finite differences of the group's derivative, perturbing one state (or parameter) at a
+	/// time.
+	FiniteDifference,
+	/// A user-supplied Jacobian.
+	///
+	/// The Jacobian must have one row per state and one column per state followed by one column per
+	/// sensitivity parameter.
+	Analytic(JacobianFn<S>),
+}
+
+impl<S> Jacobian<S> {
+	pub fn analytic<F>(jacobian: F) -> Self
+	where
+		F: Fn(&S, &SimTime) -> Matrix + 'static,
+	{
+		Self::Analytic(Box::new(jacobian))
+	}
+}
+
+/// Variational equations integrated alongside a group's state.
+pub(crate) struct Variational<S> {
+	jacobian: Jacobian<S>,
+	parameter_loader: Option<StateLoader<S>>,
+	parameter_unloader: Option<StateUnloader<S>>,
+	pub(crate) unloader: StmUnloader<S>,
+	/// The propagated matrix, `[STM | parameter sensitivities]`, since the start of the run.
+	pub(crate) psi: Option<Matrix>,
+}
+
+impl<S> Variational<S> {
+	/// Returns the initial value of the propagated matrix for a group with `n` states.
+	pub(crate) fn initial_psi(&mut self, sim: &S, time: &SimTime, n: usize) -> Matrix {
+		let p = match &self.parameter_loader {
+			Some(loader) => loader(sim, time).len(),
+			None => 0,
+		};
+
+		let mut psi = Matrix::zeros(n, n + p);
+		for i in 0..n {
+			psi[(i, i)] = 1.0;
+		}
+		psi
+	}
+
+	/// Returns the number of derivative evaluations needed to compute the Jacobian for a propagated
+	/// matrix with `cols` columns.
+	pub(crate) fn jacobian_cost(&self, cols: usize) -> u64 {
+		match self.jacobian {
+			Jacobian::FiniteDifference => 2 * cols as u64,
+			Jacobian::Analytic(_) => 0,
+		}
+	}
+
+	/// Evaluates the derivative of the propagated matrix, `A * psi + [0 | B]`, where `A` and `B` are
+	/// the Jacobians of the derivative with respect to the state and the parameters.
+	///
+	/// `sim` must hold the state `y`, and is left holding it.
+	pub(crate) fn derivative(
+		&mut self,
+		sim: &mut S,
+		y: &[f64],
+		psi: &[f64],
+		time: &SimTime,
+		derivative: &Derivative<S>,
+		state_unloader: &mut StateUnloader<S>,
+	) -> Vec<f64> {
+		let n = y.len();
+		let m = psi.len() / n.max(1);
+
+		let jacobian = match &self.jacobian {
+			Jacobian::Analytic(jacobian) => jacobian(sim, time),
+			Jacobian::FiniteDifference => {
+				let mut scratch = Deferred::new();
+				let mut columns = Vec::with_capacity(m);
+
+				let a = central_difference_jacobian(
+					|x| {
+						state_unloader(sim, x);
+						derivative(sim, time, &mut scratch)
+					},
+					y,
+				);
+				state_unloader(sim, y);
+				columns.extend((0..n).map(|j| a.column(j)));
+
+				if let (Some(loader), Some(unloader)) =
+					(&self.parameter_loader, &mut self.parameter_unloader)
+				{
+					let params = loader(sim, time);
+					let b = central_difference_jacobian(
+						|x| {
+							unloader(sim, x);
+							derivative(sim, time, &mut scratch)
+						},
+						&params,
+					);
+					unloader(sim, &params);
+					columns.extend((0..b.cols()).map(|j| b.column(j)));
+				}
+
+				Matrix::from_columns(&columns)
+			},
+		};
+
+		assert!(
+			jacobian.rows() == n && jacobian.cols() == m,
+			"Jacobian should be {n}x{m}, but was {}x{}",
+			jacobian.rows(),
+			jacobian.cols()
+		);
+
+		let psi = Matrix::from_row_major(n, m, psi.to_vec());
+		let mut d_psi = &jacobian.columns(0, n) * &psi;
+		for i in 0..n {
+			for j in n..m {
+				d_psi[(i, j)] += jacobian[(i, j)];
+			}
+		}
+
+		d_psi.as_slice().to_vec()
+	}
+}
+
+/// Counters describing the work an integration group did during a run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IntegratorStats {
+	pub group: String,
+	/// Derivative evaluations, including those used for finite-difference Jacobians.
+	pub derivative_calls: u64,
+	pub accepted_steps: u64,
+	/// Steps rejected by adaptive methods.
+	pub rejected_steps: u64,
+	/// Newton iterations taken by implicit methods.
+	pub newton_iterations: u64,
+	pub jacobian_evaluations: u64,
+	pub last_step_size: f64,
+	pub min_step_size: f64,
+	pub max_step_size: f64,
+	/// Total time covered by accepted steps.
+	pub integrated_time: f64,
+}
+
+impl IntegratorStats {
+	fn new(group: &str) -> Self {
+		Self {
+			group: group.to_string(),
+			..Default::default()
+		}
+	}
+
+	pub(crate) fn record_step(&mut self, h: f64) {
+		if self.accepted_steps == 0 {
+			self.min_step_size = h;
+			self.max_step_size = h;
+		} else {
+			self.min_step_size = self.min_step_size.min(h);
+			self.max_step_size = self.max_step_size.max(h);
+		}
+		self.accepted_steps += 1;
+		self.last_step_size = h;
+		self.integrated_time += h;
+	}
+
+	/// Returns the mean accepted step size, which is zero before the first step, like the minimum
+	/// and maximum.
+	pub fn mean_step_size(&self) -> f64 {
+		if self.accepted_steps == 0 {
+			0.0
+		} else {
+			self.integrated_time / self.accepted_steps as f64
+		}
+	}
+
+	/// Returns the value of one statistic.
+	pub fn get(&self, stat: IntegratorStat) -> f64 {
+		match stat {
+			IntegratorStat::DerivativeCalls => self.derivative_calls as f64,
+			IntegratorStat::AcceptedSteps => self.accepted_steps as f64,
+			IntegratorStat::RejectedSteps => self.rejected_steps as f64,
+			IntegratorStat::NewtonIterations => self.newton_iterations as f64,
+			IntegratorStat::JacobianEvaluations => self.jacobian_evaluations as f64,
+			IntegratorStat::StepSize => self.last_step_size,
+			IntegratorStat::MinStepSize => self.min_step_size,
+			IntegratorStat::MaxStepSize => self.max_step_size,
+			IntegratorStat::MeanStepSize => self.mean_step_size(),
+		}
+	}
+}
+
+impl fmt::Display for IntegratorStats {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{}: {} derivative calls, {} accepted steps, {} rejected steps, {} Newton iterations, {} \
+			 Jacobian evaluations, step size min/mean/max {}/{}/{}",
+			self.group,
+			self.derivative_calls,
+			self.accepted_steps,
+			self.rejected_steps,
+			self.newton_iterations,
+			self.jacobian_evaluations,
+			self.min_step_size,
+			self.mean_step_size(),
+			self.max_step_size
+		)
+	}
+}
+
+/// An integrator statistic that can be recorded as a channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntegratorStat {
+	DerivativeCalls,
+	AcceptedSteps,
+	RejectedSteps,
+	NewtonIterations,
+	JacobianEvaluations,
+	/// Size of the most recent accepted step.
+	StepSize,
+	MinStepSize,
+	MaxStepSize,
+	MeanStepSize,
+}
+
+/// A set of continuous states that are integrated together with their own step size and method.
+pub struct IntegrationGroup<S> {
+	pub(crate) name: String,
+	pub(crate) dt: Option<f64>,
+	pub(crate) method: Method,
+	pub(crate) coupling: Coupling,
+	pub(crate) states: Vec<String>,
+	pub(crate) state_loader: StateLoader<S>,
+	pub(crate) derivative: Derivative<S>,
+	pub(crate) state_unloader: StateUnloader<S>,
+	pub(crate) history: History,
+	pub(crate) variational: Option<Variational<S>>,
+	pub(crate) stats: IntegratorStats,
+}
+
+impl<S> IntegrationGroup<S> {
+	/// Creates a group that is integrated with RK4 at the executor's frame rate.
+	pub fn new<L, D, U>(name: &str, state_loader: L, derivative: D, state_unloader: U) -> Self
+	where
+		L: Fn(&S, &SimTime) -> Vec<f64> + 'static,
+		D: Fn(&S, &SimTime, &mut Deferred<S>) -> Vec<f64> + 'static,
+		U: FnMut(&mut S, &[f64]) + 'static,
+	{
+		Self {
+			name: name.to_string(),
+			dt: None,
+			method: Method::default(),
+			coupling: Coupling::default(),
+			states: Vec::new(),
+			state_loader: Box::new(state_loader),
+			derivative: Box::new(derivative),
+			state_unloader: Box::new(state_unloader),
+			history: History::default(),
+			variational: None,
+			stats: IntegratorStats::new(name),
+		}
+	}
+
+	/// Sets the integration step size of this group.
+	///
+	/// The executor's frame `dt` must be a whole multiple of the group's step size.
+	pub fn dt(mut self, dt: f64) -> Self {
+		self.dt = Some(dt);
+		self
+	}
+
+	/// # Panics
+	///
+	/// Panics if `method` is [`Method::AdamsBashforthMoulton`] with an unsupported order.
+	pub fn method(mut self, method: Method) -> Self {
+		if let Method::AdamsBashforthMoulton(order) = method {
+			assert!(
+				(1..=MAX_ABM_ORDER).contains(&order),
+				"Adams-Bashforth-Moulton order must be between 1 and {MAX_ABM_ORDER}, got {order}"
+			);
+		}
+		self.method = method;
+		self
+	}
+
+	pub fn coupling(mut self, coupling: Coupling) -> Self {
+		self.coupling = coupling;
+		self
+	}
+
+	/// Names the states returned by the state loader, in order.
+	///
+	/// Named states are registered with the executor, which uses them to report which group owns
+	/// each state.
+	pub fn states(mut self, names: &[&str]) -> Self {
+		self.states = names.iter().map(|name| name.to_string()).collect();
+		self
+	}
+
+	/// Propagates the group's state transition matrix alongside its state.
+	///
+	/// The matrix starts as the identity at the beginning of the run and is integrated with the
+	/// group's method. After every step it is handed to `stm_unloader`, so that jobs and recorders
+	/// can read it from the simulation. If sensitivity parameters are set, the matrix has one extra
+	/// column per parameter holding the sensitivity of each state to that parameter.
+	pub fn stm<U>(mut self, jacobian: Jacobian<S>, stm_unloader: U) -> Self
+	where
+		U: FnMut(&mut S, &Matrix) + 'static,
+	{
+		let variational = self.variational.get_or_insert_with(|| Variational {
+			jacobian: Jacobian::FiniteDifference,
+			parameter_loader: None,
+			parameter_unloader: None,
+			unloader: Box::new(|_, _| {}),
+			psi: None,
+		});
+		variational.jacobian = jacobian;
+		variational.unloader = Box::new(stm_unloader);
+		self
+	}
+
+	/// Sets the parameters whose sensitivities are propagated with the state transition matrix.
+	///
+	/// # Panics
+	///
+	/// Panics if [`IntegrationGroup::stm()`] has not been called first.
+	pub fn sensitivity_parameters<L, U>(
+		mut self,
+		parameter_loader: L,
+		parameter_unloader: U,
+	) -> Self
+	where
+		L: Fn(&S, &SimTime) -> Vec<f64> + 'static,
+		U: FnMut(&mut S, &[f64]) + 'static,
+	{
+		let variational = self
+			.variational
+			.as_mut()
+			.expect("the state transition matrix must be enabled before adding parameters");
+		variational.parameter_loader = Some(Box::new(parameter_loader));
+		variational.parameter_unloader = Some(Box::new(parameter_unloader));
+		self
+	}
+
+	/// Clears everything the group carries over between steps.
+	pub(crate) fn reset(&mut self) {
+		self.history.clear();
+		self.stats = IntegratorStats::new(&self.name);
+		if let Some(variational) = &mut self.variational {
+			variational.psi = None;
+		}
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn state_names(&self) -> &[String] {
+		&self.states
+	}
+}
+
+/// Derivative history kept between steps by multistep methods.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct History {
+	h: f64,
+	t: f64,
+	y: Vec<f64>,
+	// newest first: f(t), f(t - h), f(t - 2h), ...
+	derivatives: VecDeque<Vec<f64>>,
+}
+
+impl History {
+	pub(crate) fn clear(&mut self) {
+		self.derivatives.clear();
+	}
+
+	/// Returns true if a step of size `h` from state `y` at time `t` continues from the previous
+	/// step.
+	fn continues(&self, y: &[f64], t: f64, h: f64) -> bool {
+		let tolerance = 1e-9 * h.abs();
+		// states only differ by rounding after a round trip through the simulation, which can
+		// store them in other units
+		let unchanged = |(a, b): (&f64, &f64)| (a - b).abs() <= 1e-12 * a.abs().max(b.abs());
+		!self.derivatives.is_empty()
+			&& (self.h - h).abs() <= tolerance
+			&& (self.t - t).abs() <= tolerance
+			&& self.y.len() == y.len()
+			&& self.y.iter().zip(y).all(unchanged)
+	}
+}
+
+const MAX_ABM_ORDER: usize = 5;
+
+// Adams-Bashforth coefficients, applied to f(t), f(t - h), ...
+const ADAMS_BASHFORTH: [&[f64]; MAX_ABM_ORDER] = [
+	&[1.0],
+	&[3.0 / 2.0, -1.0 / 2.0],
+	&[23.0 / 12.0, -16.0 / 12.0, 5.0 / 12.0],
+	&[55.0 / 24.0, -59.0 / 24.0, 37.0 / 24.0, -9.0 / 24.0],
+	&[
+		1901.0 / 720.0,
+		-2774.0 / 720.0,
+		2616.0 / 720.0,
+		-1274.0 / 720.0,
+		251.0 / 720.0,
+	],
+];
+
+// Adams-Moulton coefficients, applied to f(t + h), f(t), f(t - h), ...
+const ADAMS_MOULTON: [&[f64]; MAX_ABM_ORDER] = [
+	&[1.0],
+	&[1.0 / 2.0, 1.0 / 2.0],
+	&[5.0 / 12.0, 8.0 / 12.0, -1.0 / 12.0],
+	&[9.0 / 24.0, 19.0 / 24.0, -5.0 / 24.0, 1.0 / 24.0],
+	&[
+		251.0 / 720.0,
+		646.0 / 720.0,
+		-264.0 / 720.0,
+		106.0 / 720.0,
+		-19.0 / 720.0,
+	],
+];
+
+/// Advances the state vector `y` by one step of size `h` starting at `time`.
+///
+/// `f` evaluates the derivative of the state vector at a given time, and is told whether the state
+/// is one the integrator has accepted rather than a trial state it may throw away. `history` is
+/// only used by multistep methods.
+pub(crate) fn step<F>(
+	method: Method,
+	f: &mut F,
+	y: &[f64],
+	time: &SimTime,
+	h: f64,
+	history: &mut History,
+) -> Vec<f64>
+where
+	F: FnMut(&[f64], &SimTime, bool) -> Vec<f64>,
+{
+	match method {
+		Method::Euler => euler_step(f, y, time, h),
+		Method::RungeKutta4 => {
+			let k1 = f(y, &SimTime { dt: h, ..*time }, true);
+			rk4_step(&mut |y, time| f(y, time, false), y, &k1, time, h)
+		},
+		Method::AdamsBashforthMoulton(order) => abm_step(order, f, y, time, h, history),
+	}
+}
+
+fn euler_step<F>(f: &mut F, y: &[f64], time: &SimTime, h: f64) -> Vec<f64>
+where
+	F: FnMut(&[f64], &SimTime, bool) -> Vec<f64>,
+{
+	let t_0 = &SimTime { dt: h, ..*time };
+	let k1 = f(y, t_0, true);
+
+	y.iter().zip(&k1).map(|(y, k1)| y + h * k1).collect()
+}
+
+fn abm_step<F>(
+	order: usize,
+	f: &mut F,
+	y: &[f64],
+	time: &SimTime,
+	h: f64,
+	history: &mut History,
+) -> Vec<f64>
+where
+	F: FnMut(&[f64], &SimTime, bool) -> Vec<f64>,
+{
+	let t_0 = &SimTime { dt: h, ..*time };
+	let t_full = &SimTime {
+		t: time.t + h,
+		dt: h,
+		..*time
+	};
+
+	// a discontinuity (or the first step) invalidates every stored derivative
+	if !history.continues(y, time.t, h) {
+		history.derivatives.clear();
+		history.derivatives.push_front(f(y, t_0, true));
+	}
+
+	let res = if history.derivatives.len() < order {
+		// not enough history yet, so start up with RK4
+		rk4_step(&mut |y, time| f(y, time, false), y, &history.derivatives[0], time, h)
+	} else {
+		let n = y.len();
+		let past = &history.derivatives;
+
+		// predict
+		let ab = ADAMS_BASHFORTH[order - 1];
+		let predicted: Vec<f64> = (0..n)
+			.map(|i| y[i] + h * ab.iter().zip(past).map(|(c, d)| c * d[i]).sum::<f64>())
+			.collect();
+
+		// evaluate
+		let f_predicted = f(&predicted, t_full, false);
+
+		// correct
+		let am = ADAMS_MOULTON[order - 1];
+		(0..n)
+			.map(|i| {
+				let correction = am[1..].iter().zip(past).map(|(c, d)| c * d[i]).sum::<f64>();
+				y[i] + h * (am[0] * f_predicted[i] + correction)
+			})
+			.collect()
+	};
+
+	// evaluate at the accepted state, which becomes f(t) for the next step
+	history.derivatives.push_front(f(&res, t_full, true));
+	history.derivatives.truncate(order);
+	history.h = h;
+	history.t = time.t + h;
+	history.y.clone_from(&res);
+
+	res
+}
+
+/// Takes an RK4 step given the derivative `k1` at the start of the step.
+fn rk4_step<F>(f: &mut F, y: &[f64], k1: &[f64], time: &SimTime, h: f64) -> Vec<f64>
+where
+	F: FnMut(&[f64], &SimTime) -> Vec<f64>,
+{
+	// we want to pass in the current integration dt to the functions, which might be different from
+	// the simulation's default dt
+	let t_half = &SimTime {
+		t: time.t + (0.5 * h),
+		dt: h,
+		..*time
+	};
+	let t_full = &SimTime {
+		t: time.t + h,
+		dt: h,
+		..*time
+	};
+
+	let n = y.len();
+
+	let y2: Vec<f64> = (0..n).map(|i| y[i] + 0.5 * h * k1[i]).collect();
+	let k2 = f(&y2, t_half);
+
+	let y3: Vec<f64> = (0..n).map(|i| y[i] + 0.5 * h * k2[i]).collect();
+	let k3 = f(&y3, t_half);
+
+	let y4: Vec<f64> = (0..n).map(|i| y[i] + h * k3[i]).collect();
+	let k4 = f(&y4, t_full);
+
+	(0..n)
+		.map(|i| y[i] + (h / 6.0) * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]))
+		.collect()
+}
+
+/// Integrates the simulation by one RK4 step of size `dt`.
+///
+/// Updates deferred by the derivative at the start of the step are applied after the step.
 pub fn runge_kutta_4<S, D, L, U>(
 	sim: &mut S,
 	state_loader: &L,
@@ -8,50 +657,272 @@ pub fn runge_kutta_4<S, D, L, U>(
 	dt: f64,
 	sim_time: &SimTime,
 ) where
-	L: Fn(&mut S, &SimTime) -> Vec<f64>,
-	D: Fn(&mut S, &SimTime) -> Vec<f64>,
+	L: Fn(&S, &SimTime) -> Vec<f64>,
+	D: Fn(&S, &SimTime, &mut Deferred<S>) -> Vec<f64>,
 	U: FnMut(&mut S, &[f64]),
 {
-	// we want to pass in the current integration dt to the functions, which might be different from
-	// the simulation's default dt
-	let t_0 = &SimTime { dt, ..*sim_time };
-	let t_half = &SimTime {
-		t: sim_time.t + (0.5 * dt),
-		dt,
-		..*sim_time
-	};
-	let t_full = &SimTime {
-		t: sim_time.t + dt,
-		dt,
-		..*sim_time
+	let state = state_loader(sim, &SimTime { dt, ..*sim_time });
+	let mut deferred = Deferred::new();
+	let k1 = derivative(sim, &SimTime { dt, ..*sim_time }, &mut deferred);
+
+	// updates requested at the intermediate stages are thrown away with their trial states
+	let mut scratch = Deferred::new();
+	let mut f = |y: &[f64], time: &SimTime| {
+		state_unloader(sim, y);
+		derivative(sim, time, &mut scratch)
 	};
+	let res = rk4_step(&mut f, &state, &k1, sim_time, dt);
 
-	let state = state_loader(sim, t_0);
-	let n = state.len();
+	state_unloader(sim, &res);
+	deferred.commit(sim);
+}
 
-	let k1 = derivative(sim, t_0);
+#[cfg(test)]
+mod tests {
+	use super::*;
 
-	let y2: Vec<f64> = (0..n).map(|i| state[i] + 0.5 * dt * k1[i]).collect();
-	state_unloader(sim, &y2);
-	let k2 = derivative(sim, t_half);
+	fn decay(y: &[f64], _: &SimTime, _: bool) -> Vec<f64> {
+		vec![-y[0]]
+	}
 
-	let y3: Vec<f64> = (0..n).map(|i| state[i] + 0.5 * dt * k2[i]).collect();
-	state_unloader(sim, &y3);
-	let k3 = derivative(sim, t_half);
+	fn integrate(method: Method, h: f64, steps: u64) -> f64 {
+		let mut history = History::default();
+		let mut y = vec![1.0];
+		for step_idx in 0..steps {
+			let time = SimTime {
+				t: step_idx as f64 * h,
+				dt: h,
+				step: step_idx,
+			};
+			y = step(method, &mut decay, &y, &time, h, &mut history);
+		}
+		y[0]
+	}
 
-	let y4: Vec<f64> = (0..n).map(|i| state[i] + dt * k3[i]).collect();
-	state_unloader(sim, &y4);
-	let k4 = derivative(sim, t_full);
+	#[test]
+	fn adams_bashforth_moulton_converges_at_its_order() {
+		let exact = f64::exp(-1.0);
+		for order in 1..=5 {
+			let method = Method::AdamsBashforthMoulton(order);
+			let coarse = (integrate(method, 0.02, 50) - exact).abs();
+			let fine = (integrate(method, 0.01, 100) - exact).abs();
+			let observed = (coarse / fine).log2();
+			assert!((observed - order as f64).abs() < 0.3, "order {order} converged at {observed}");
+		}
+	}
 
-	let res: Vec<f64> = (0..n)
-		.map(|i| state[i] + (dt / 6.0) * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]))
-		.collect();
+	#[test]
+	fn adams_bashforth_moulton_restarts_after_state_change() {
+		let method = Method::AdamsBashforthMoulton(3);
+		let mut history = History::default();
+		let h = 0.1;
+		let mut y = vec![1.0];
+		for step_idx in 0..4 {
+			let time = SimTime {
+				t: step_idx as f64 * h,
+				dt: h,
+				step: step_idx,
+			};
+			y = step(method, &mut decay, &y, &time, h, &mut history);
+		}
+		assert_eq!(history.derivatives.len(), 3);
 
-	state_unloader(sim, &res);
-}
+		// modifying the state outside the integrator throws away the history
+		y[0] += 1.0;
+		let time = SimTime {
+			t: 0.4,
+			dt: h,
+			step: 4,
+		};
+		step(method, &mut decay, &y, &time, h, &mut history);
+		assert_eq!(history.derivatives.len(), 2);
+	}
+
+	/// Runs `x' = -x` from 1 with ABM(3) in 0.1 s steps for 2 s, with the state kept in
+	/// thousandths so it is rounded on every round trip, and optionally reset to 1 at 1 s.
+	fn decay_with_reset(reset: bool) -> (f64, u64) {
+		let mut exec = crate::Executor::<f64>::new(0.1, 2.0);
+		exec.add_integration_group(
+			IntegrationGroup::new(
+				"decay",
+				|milli: &f64, _| vec![milli * 1000.0],
+				|milli, _, _| vec![-milli * 1000.0],
+				|milli, y| *milli = y[0] / 1000.0,
+			)
+			.method(Method::AdamsBashforthMoulton(3)),
+		);
+		exec.add_job(crate::Phase::PreIntegrate, move |milli, time| {
+			if reset && time.step == 10 {
+				*milli = 1e-3;
+			}
+		});
+		let last = std::rc::Rc::new(std::cell::Cell::new(0.0));
+		let last_milli = std::rc::Rc::clone(&last);
+		exec.add_job(crate::Phase::Shutdown, move |milli, _| last_milli.set(*milli));
+		let calls = exec.run(1e-3).integrator_stats[0].derivative_calls;
+		(last.get() * 1000.0, calls)
+	}
+
+	#[test]
+	fn adams_bashforth_moulton_restarts_only_when_a_job_changes_the_state() {
+		// 5 evaluations for the first RK4 start-up step, 4 for the second, then 2 per PECE step
+		let (x, calls) = decay_with_reset(false);
+		assert!((x - f64::exp(-2.0)).abs() < 1e-4, "{x}");
+		assert_eq!(calls, 5 + 4 + 18 * 2);
+
+		// the reset starts the history again, so the second second decays from 1 like the first
+		let (x, calls) = decay_with_reset(true);
+		assert!((x - f64::exp(-1.0)).abs() < 1e-4, "{x}");
+		assert_eq!(calls, 2 * (5 + 4 + 8 * 2));
+	}
+
+	/// Runs `x' = 1` from 0 for three 1 s steps, with a counter in the second element that the
+	/// derivative increments through `Deferred` whenever `request` holds, returning the counter
+	/// after each step.
+	fn count_deferred(method: Method, request: fn(&(f64, u32)) -> bool) -> Vec<f64> {
+		let mut exec = crate::Executor::<(f64, u32)>::new(1.0, 3.0);
+		exec.add_integration_group(
+			IntegrationGroup::new(
+				"counter",
+				|s: &(f64, u32), _| vec![s.0],
+				move |s, _, updates| {
+					if request(s) {
+						updates.defer(|s| s.1 += 1);
+					}
+					vec![1.0]
+				},
+				|s, y| s.0 = y[0],
+			)
+			.method(method),
+		);
+		let mut recorder = crate::Recorder::in_memory();
+		recorder.track("count", |s: &(f64, u32)| f64::from(s.1));
+		exec.add_recorder(recorder);
+		let result = exec.run((0.0, 0));
+		result.recordings[0].column("count").unwrap()[1..].to_vec()
+	}
+
+	#[test]
+	fn deferred_updates_are_applied_once_per_request() {
+		for method in [Method::Euler, Method::RungeKutta4] {
+			assert_eq!(count_deferred(method, |_| true), [1.0, 2.0, 3.0], "{method:?}");
+		}
+	}
+
+	#[test]
+	fn deferred_updates_from_trial_states_are_discarded() {
+		// the intermediate stages of the first step reach x = 0.5, but its accepted states do not
+		let crossed = |s: &(f64, u32)| s.0 >= 0.5 && s.1 == 0;
+		assert_eq!(count_deferred(Method::RungeKutta4, crossed), [0.0, 1.0, 1.0]);
+		assert_eq!(count_deferred(Method::AdamsBashforthMoulton(2), crossed), [1.0, 1.0, 1.0]);
+
+		// the predicted state of a continuing step is a trial state too
+		let past_two = |s: &(f64, u32)| s.0 > 2.0 && s.1 == 0;
+		assert_eq!(count_deferred(Method::AdamsBashforthMoulton(2), past_two), [0.0, 0.0, 1.0]);
+	}
+
+	#[derive(Clone, Default)]
+	struct Decay {
+		x: f64,
+		k: f64,
+		// the state transition matrix and the sensitivity of x to k
+		stm: f64,
+		dx_dk: f64,
+	}
+
+	/// Runs `x' = -k x` from `x0 = 2` with `k = 0.5` for 1 s, returning the final state transition
+	/// matrix and sensitivity to `k`.
+	fn propagate_decay(jacobian: Jacobian<Decay>) -> (f64, f64) {
+		let mut exec = crate::Executor::<Decay>::new(0.01, 1.0);
+		exec.add_integration_group(
+			IntegrationGroup::new(
+				"decay",
+				|s: &Decay, _| vec![s.x],
+				|s, _, _| vec![-s.k * s.x],
+				|s, y| s.x = y[0],
+			)
+			.stm(jacobian, |s, psi| (s.stm, s.dx_dk) = (psi[(0, 0)], psi[(0, 1)]))
+			.sensitivity_parameters(|s, _| vec![s.k], |s, p| s.k = p[0]),
+		);
+		let mut recorder = crate::Recorder::in_memory();
+		recorder.track("stm", |s: &Decay| s.stm);
+		recorder.track("dx_dk", |s: &Decay| s.dx_dk);
+		exec.add_recorder(recorder);
+
+		let decay = Decay {
+			x: 2.0,
+			k: 0.5,
+			..Default::default()
+		};
+		let result = exec.run(decay);
+		let last = |name| *result.recordings[0].column(name).unwrap().last().unwrap();
+		(last("stm"), last("dx_dk"))
+	}
+
+	#[test]
+	fn stm_and_sensitivity_match_the_analytic_solution() {
+		// x = x0 e^{-kt}, so dx/dx0 = e^{-kt} and dx/dk = -t x0 e^{-kt}
+		let (t, x0, k) = (1.0, 2.0, 0.5);
+		let stm = f64::exp(-k * t);
+		let dx_dk = -t * x0 * f64::exp(-k * t);
+
+		let analytic =
+			Jacobian::analytic(|s: &Decay, _| Matrix::from_row_major(1, 2, vec![-s.k, -s.x]));
+		for jacobian in [Jacobian::FiniteDifference, analytic] {
+			let (propagated_stm, propagated_dx_dk) = propagate_decay(jacobian);
+			assert!((propagated_stm - stm).abs() < 1e-9, "{propagated_stm} != {stm}");
+			assert!((propagated_dx_dk - dx_dk).abs() < 1e-9, "{propagated_dx_dk} != {dx_dk}");
+		}
+	}
+
+	/// Runs the executor, returning the statistics of its group and the recorded step counts and
+	/// derivative calls.
+	fn run_with_stats(exec: &mut crate::Executor<f64>) -> (IntegratorStats, Vec<f64>, Vec<f64>) {
+		let result = exec.run(1.0);
+		let data = &result.recordings[0];
+		let steps = data.column("steps").unwrap().to_vec();
+		let calls = data.column("calls").unwrap().to_vec();
+		// the initial sample is taken before any step
+		assert_eq!(data.value_at("mean_dt", 0.0), Some(0.0));
+		(result.integrator_stats[0].clone(), steps, calls)
+	}
+
+	#[test]
+	fn stats_count_steps_and_evaluations() {
+		// ABM(3) starts with two RK4 steps, the first also evaluating the initial derivative, then
+		// takes PECE steps with two evaluations each
+		for (method, calls) in [
+			(Method::RungeKutta4, [0.0, 4.0, 8.0, 12.0, 16.0, 20.0]),
+			(Method::AdamsBashforthMoulton(3), [0.0, 5.0, 9.0, 11.0, 13.0, 15.0]),
+		] {
+			let mut exec = crate::Executor::<f64>::new(0.1, 0.5);
+			exec.add_integration_group(
+				IntegrationGroup::new(
+					"x",
+					|x: &f64, _| vec![*x],
+					|x, _, _| vec![-x],
+					|x, y| *x = y[0],
+				)
+				.method(method),
+			);
+			let mut recorder = crate::Recorder::in_memory();
+			recorder.track_integrator("steps", "x", IntegratorStat::AcceptedSteps);
+			recorder.track_integrator("calls", "x", IntegratorStat::DerivativeCalls);
+			recorder.track_integrator("mean_dt", "x", IntegratorStat::MeanStepSize);
+			exec.add_recorder(recorder);
 
-pub struct Integrator<S> {
-	pub state_loader: Box<dyn Fn(&mut S, &SimTime) -> Vec<f64>>,
-	pub derivative: Box<dyn Fn(&mut S, &SimTime) -> Vec<f64>>,
-	pub state_unloader: Box<dyn FnMut(&mut S, &[f64])>,
+			// the counters start again with every run
+			for _ in 0..2 {
+				let (stats, recorded_steps, recorded_calls) = run_with_stats(&mut exec);
+				assert_eq!(stats.accepted_steps, 5, "{method:?}");
+				assert_eq!(stats.derivative_calls, calls[5] as u64, "{method:?}");
+				assert_eq!((stats.rejected_steps, stats.newton_iterations), (0, 0));
+				assert_eq!(stats.jacobian_evaluations, 0);
+				assert_eq!((stats.min_step_size, stats.max_step_size), (0.1, 0.1));
+				assert!((stats.integrated_time - 0.5).abs() < 1e-12);
+				assert_eq!(recorded_steps, [0.0, 1.0, 2.0, 3.0, 4.0, 5.0], "{method:?}");
+				assert_eq!(recorded_calls, calls, "{method:?}");
+			}
+		}
+	}
 }
@@ -1,7 +1,39 @@
+extern crate self as simlib;
+
+mod compare;
+mod convergence;
+mod events;
 mod executor;
+mod health;
 mod integrator;
+mod linalg;
+mod linearize;
+mod metrics;
+mod plot;
+mod recordable;
 mod recorder;
+mod run_data;
+mod units;
 
-pub use executor::{Executor, Phase, SimTime};
-pub use integrator::runge_kutta_4;
-pub use recorder::Recorder;
+pub use compare::{BLESS_VAR, ChannelComparison, Comparison, ComparisonReport};
+pub use convergence::{ChannelConvergence, ConvergenceReport, ConvergenceStudy};
+pub use events::{Event, EventLog, Severity};
+pub use executor::{Executor, Phase, RunResult, RunStatus, SimTime};
+pub use health::{NonFinite, NonFiniteAction, NonFiniteSource};
+pub use integrator::{
+	Coupling, Deferred, IntegrationGroup, IntegratorStat, IntegratorStats, Jacobian, Method,
+	runge_kutta_4,
+};
+pub use linalg::{Complex, Matrix};
+pub use linearize::{Inputs, Linearization, Trim, TrimError, TrimResult};
+pub use metrics::{Metric, MetricStatistics, MetricValue, Reduction, Summary, SummaryTable};
+pub use plot::Plot;
+pub use recordable::{Recordable, Variable};
+pub use recorder::{
+	ChannelType, Components, CsvOptions, Format, INDEX_STRIDE, Notation, Recorder, RecordingReader,
+	SamplePoint, TRICK_TIME, Tracked, TrickReader, Value,
+};
+pub use run_data::{Column, RunData};
+#[cfg(feature = "derive")]
+pub use simlib_derive::Recordable;
+pub use units::{Angle, Force, Length, Mass, Pressure, Quantity, Temperature, Time, UnitError};
@@ -0,0 +1,538 @@
+//! A small dense matrix type for analysis utilities.
+
+use std::{
+	fmt,
+	ops::{Index, IndexMut, Mul},
+};
+
+/// A complex number, used for eigenvalues.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Complex {
+	pub re: f64,
+	pub im: f64,
+}
+
+impl Complex {
+	pub fn new(re: f64, im: f64) -> Self {
+		Self { re, im }
+	}
+
+	pub fn abs(&self) -> f64 {
+		self.re.hypot(self.im)
+	}
+
+	/// Returns the natural frequency (rad/s) of the mode with this eigenvalue.
+	pub fn natural_frequency(&self) -> f64 {
+		self.abs()
+	}
+
+	/// Returns the damping ratio of the mode with this eigenvalue.
+	pub fn damping_ratio(&self) -> f64 {
+		-self.re / self.abs()
+	}
+}
+
+impl fmt::Display for Complex {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if self.im < 0.0 {
+			write!(f, "{} - {}i", self.re, -self.im)
+		} else {
+			write!(f, "{} + {}i", self.re, self.im)
+		}
+	}
+}
+
+/// A dense, row-major matrix of `f64`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix {
+	rows: usize,
+	cols: usize,
+	data: Vec<f64>,
+}
+
+impl Matrix {
+	pub fn zeros(rows: usize, cols: usize) -> Self {
+		Self {
+			rows,
+			cols,
+			data: vec![0.0; rows * cols],
+		}
+	}
+
+	pub fn identity(n: usize) -> Self {
+		let mut m = Self::zeros(n, n);
+		for i in 0..n {
+			m[(i, i)] = 1.0;
+		}
+		m
+	}
+
+	/// Creates a matrix from its elements in row-major order.
+	///
+	/// # Panics
+	///
+	/// Panics if `data` does not have `rows * cols` elements.
+	pub fn from_row_major(rows: usize, cols: usize, data: Vec<f64>) -> Self {
+		assert!(data.len() == rows * cols, "expected {} elements, got {}", rows * cols, data.len());
+		Self { rows, cols, data }
+	}
+
+	/// Creates a matrix from its columns.
+	///
+	/// # Panics
+	///
+	/// Panics if the columns do not all have the same length.
+	pub fn from_columns(columns: &[Vec<f64>]) -> Self {
+		let rows = columns.first().map_or(0, Vec::len);
+		let mut m = Self::zeros(rows, columns.len());
+		for (j, column) in columns.iter().enumerate() {
+			assert!(column.len() == rows, "matrix columns must all have the same length");
+			for (i, x) in column.iter().enumerate() {
+				m[(i, j)] = *x;
+			}
+		}
+		m
+	}
+
+	pub fn rows(&self) -> usize {
+		self.rows
+	}
+
+	pub fn cols(&self) -> usize {
+		self.cols
+	}
+
+	/// Returns the elements in row-major order.
+	pub fn as_slice(&self) -> &[f64] {
+		&self.data
+	}
+
+	pub fn row(&self, i: usize) -> &[f64] {
+		&self.data[i * self.cols..(i + 1) * self.cols]
+	}
+
+	pub fn column(&self, j: usize) -> Vec<f64> {
+		(0..self.rows).map(|i| self[(i, j)]).collect()
+	}
+
+	pub fn transpose(&self) -> Self {
+		let mut t = Self::zeros(self.cols, self.rows);
+		for i in 0..self.rows {
+			for j in 0..self.cols {
+				t[(j, i)] = self[(i, j)];
+			}
+		}
+		t
+	}
+
+	/// Returns the columns `start..end` as a new matrix.
+	pub fn columns(&self, start: usize, end: usize) -> Self {
+		let mut m = Self::zeros(self.rows, end - start);
+		for i in 0..self.rows {
+			for j in start..end {
+				m[(i, j - start)] = self[(i, j)];
+			}
+		}
+		m
+	}
+
+	/// Solves `self * x = b` with Gaussian elimination and partial pivoting.
+	///
+	/// Returns `None` if the matrix is singular.
+	///
+	/// # Panics
+	///
+	/// Panics if the matrix is not square or `b` does not have one element per row.
+	pub fn solve(&self, b: &[f64]) -> Option<Vec<f64>> {
+		let n = self.rows;
+		assert!(self.cols == n, "only square systems can be solved");
+		assert!(b.len() == n, "right-hand side length does not match matrix rows");
+
+		let mut a = self.clone();
+		let mut x = b.to_vec();
+
+		for col in 0..n {
+			let pivot =
+				(col..n).max_by(|&i, &j| a[(i, col)].abs().total_cmp(&a[(j, col)].abs()))?;
+			if a[(pivot, col)] == 0.0 {
+				return None;
+			}
+			if pivot != col {
+				for j in 0..n {
+					a.data.swap(pivot * n + j, col * n + j);
+				}
+				x.swap(pivot, col);
+			}
+
+			for i in col + 1..n {
+				let factor = a[(i, col)] / a[(col, col)];
+				for j in col..n {
+					a[(i, j)] -= factor * a[(col, j)];
+				}
+				x[i] -= factor * x[col];
+			}
+		}
+
+		for i in (0..n).rev() {
+			let sum: f64 = (i + 1..n).map(|j| a[(i, j)] * x[j]).sum();
+			x[i] = (x[i] - sum) / a[(i, i)];
+		}
+
+		Some(x)
+	}
+
+	/// Returns the eigenvalues of a square matrix.
+	///
+	/// The matrix is reduced to upper Hessenberg form, then the eigenvalues are found with the
+	/// shifted QR algorithm. Returns `None` if the QR iterations do not converge.
+	///
+	/// # Panics
+	///
+	/// Panics if the matrix is not square.
+	pub fn eigenvalues(&self) -> Option<Vec<Complex>> {
+		assert!(self.rows == self.cols, "eigenvalues are only defined for square matrices");
+		let mut a = self.clone();
+		a.reduce_to_hessenberg();
+		a.hessenberg_eigenvalues()
+	}
+
+	/// Reduces a square matrix to upper Hessenberg form by elimination with pivoting, which
+	/// preserves its eigenvalues.
+	fn reduce_to_hessenberg(&mut self) {
+		let n = self.rows;
+		let a = self;
+
+		for m in 1..n.saturating_sub(1) {
+			let mut x: f64 = 0.0;
+			let mut pivot = m;
+			for j in m..n {
+				if a[(j, m - 1)].abs() > x.abs() {
+					x = a[(j, m - 1)];
+					pivot = j;
+				}
+			}
+
+			if pivot != m {
+				for j in m - 1..n {
+					a.data.swap(pivot * n + j, m * n + j);
+				}
+				for i in 0..n {
+					a.data.swap(i * n + pivot, i * n + m);
+				}
+			}
+
+			if x != 0.0 {
+				for i in m + 1..n {
+					let y = a[(i, m - 1)] / x;
+					if y != 0.0 {
+						for j in m..n {
+							a[(i, j)] -= y * a[(m, j)];
+						}
+						for j in 0..n {
+							a[(j, m)] += y * a[(j, i)];
+						}
+					}
+				}
+			}
+
+			// the eliminated elements are no longer needed
+			for i in m + 1..n {
+				a[(i, m - 1)] = 0.0;
+			}
+		}
+	}
+
+	/// Finds the eigenvalues of an upper Hessenberg matrix with the Francis double-shift QR
+	/// algorithm, deflating one or two eigenvalues at a time from the bottom of the matrix.
+	fn hessenberg_eigenvalues(mut self) -> Option<Vec<Complex>> {
+		const MAX_ITERATIONS: usize = 60;
+
+		let n = self.rows;
+		let a = &mut self;
+		let mut eigenvalues = vec![Complex::new(0.0, 0.0); n];
+
+		let mut norm = 0.0;
+		for i in 0..n {
+			for j in i.saturating_sub(1)..n {
+				norm += a[(i, j)].abs();
+			}
+		}
+
+		// accumulated exceptional shifts
+		let mut shift = 0.0;
+
+		let mut nn = n as isize - 1;
+		while nn >= 0 {
+			let mut iterations = 0;
+			loop {
+				let last = nn as usize;
+
+				// look for a single small subdiagonal element to split the matrix at
+				let mut l = last;
+				while l >= 1 {
+					let mut s = a[(l - 1, l - 1)].abs() + a[(l, l)].abs();
+					if s == 0.0 {
+						s = norm;
+					}
+					if a[(l, l - 1)].abs() + s == s {
+						a[(l, l - 1)] = 0.0;
+						break;
+					}
+					l -= 1;
+				}
+
+				let mut x = a[(last, last)];
+				if l == last {
+					// one real root
+					eigenvalues[last] = Complex::new(x + shift, 0.0);
+					nn -= 1;
+				} else {
+					let mut y = a[(last - 1, last - 1)];
+					let mut w = a[(last, last - 1)] * a[(last - 1, last)];
+
+					if l == last - 1 {
+						// two roots from the trailing 2x2 block
+						let p = 0.5 * (y - x);
+						let q = p * p + w;
+						let z = q.abs().sqrt();
+						x += shift;
+						if q >= 0.0 {
+							let z = p + z.copysign(p);
+							let other = if z != 0.0 { x - w / z } else { x + z };
+							eigenvalues[last - 1] = Complex::new(x + z, 0.0);
+							eigenvalues[last] = Complex::new(other, 0.0);
+						} else {
+							eigenvalues[last - 1] = Complex::new(x + p, -z);
+							eigenvalues[last] = Complex::new(x + p, z);
+						}
+						nn -= 2;
+					} else {
+						if iterations == MAX_ITERATIONS {
+							return None;
+						}
+						if iterations == 10 || iterations == 20 {
+							// exceptional shift to break cycles
+							shift += x;
+							for i in 0..=last {
+								a[(i, i)] -= x;
+							}
+							let s = a[(last, last - 1)].abs() + a[(last - 1, last - 2)].abs();
+							x = 0.75 * s;
+							y = x;
+							w = -0.4375 * s * s;
+						}
+						iterations += 1;
+
+						// form the shift and look for two consecutive small subdiagonal elements
+						let mut m = last - 2;
+						let (mut p, mut q, mut r);
+						loop {
+							let z = a[(m, m)];
+							let r_ = x - z;
+							let s_ = y - z;
+							p = (r_ * s_ - w) / a[(m + 1, m)] + a[(m, m + 1)];
+							q = a[(m + 1, m + 1)] - z - r_ - s_;
+							r = a[(m + 2, m + 1)];
+							let s = p.abs() + q.abs() + r.abs();
+							p /= s;
+							q /= s;
+							r /= s;
+							if m == l {
+								break;
+							}
+							let u = a[(m, m - 1)].abs() * (q.abs() + r.abs());
+							let v = p.abs()
+								* (a[(m - 1, m - 1)].abs() + z.abs() + a[(m + 1, m + 1)].abs());
+							if u + v == v {
+								break;
+							}
+							m -= 1;
+						}
+
+						for i in m + 2..=last {
+							a[(i, i - 2)] = 0.0;
+							if i != m + 2 {
+								a[(i, i - 3)] = 0.0;
+							}
+						}
+
+						// double QR step on rows l..=last and columns m..=last
+						for k in m..last {
+							if k != m {
+								p = a[(k, k - 1)];
+								q = a[(k + 1, k - 1)];
+								r = if k != last - 1 {
+									a[(k + 2, k - 1)]
+								} else {
+									0.0
+								};
+								x = p.abs() + q.abs() + r.abs();
+								if x != 0.0 {
+									p /= x;
+									q /= x;
+									r /= x;
+								}
+							}
+
+							let s = (p * p + q * q + r * r).sqrt().copysign(p);
+							if s == 0.0 {
+								continue;
+							}
+
+							if k == m {
+								if l != m {
+									a[(k, k - 1)] = -a[(k, k - 1)];
+								}
+							} else {
+								a[(k, k - 1)] = -s * x;
+							}
+							p += s;
+							x = p / s;
+							y = q / s;
+							let z = r / s;
+							q /= p;
+							r /= p;
+
+							// row modification
+							for j in k..=last {
+								let mut p = a[(k, j)] + q * a[(k + 1, j)];
+								if k != last - 1 {
+									p += r * a[(k + 2, j)];
+									a[(k + 2, j)] -= p * z;
+								}
+								a[(k + 1, j)] -= p * y;
+								a[(k, j)] -= p * x;
+							}
+
+							// column modification
+							for i in l..=last.min(k + 3) {
+								let mut p = x * a[(i, k)] + y * a[(i, k + 1)];
+								if k != last - 1 {
+									p += z * a[(i, k + 2)];
+									a[(i, k + 2)] -= p * r;
+								}
+								a[(i, k + 1)] -= p * q;
+								a[(i, k)] -= p;
+							}
+						}
+					}
+				}
+
+				if nn < 0 || l as isize >= nn - 1 {
+					break;
+				}
+			}
+		}
+
+		Some(eigenvalues)
+	}
+
+	pub fn mul_vec(&self, x: &[f64]) -> Vec<f64> {
+		assert!(x.len() == self.cols, "vector length does not match matrix columns");
+		(0..self.rows)
+			.map(|i| self.row(i).iter().zip(x).map(|(a, b)| a * b).sum())
+			.collect()
+	}
+}
+
+impl Index<(usize, usize)> for Matrix {
+	type Output = f64;
+
+	fn index(&self, (i, j): (usize, usize)) -> &f64 {
+		assert!(i < self.rows && j < self.cols, "index ({i}, {j}) is out of bounds");
+		&self.data[i * self.cols + j]
+	}
+}
+
+impl IndexMut<(usize, usize)> for Matrix {
+	fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut f64 {
+		assert!(i < self.rows && j < self.cols, "index ({i}, {j}) is out of bounds");
+		&mut self.data[i * self.cols + j]
+	}
+}
+
+impl Mul for &Matrix {
+	type Output = Matrix;
+
+	fn mul(self, rhs: &Matrix) -> Matrix {
+		assert!(self.cols == rhs.rows, "matrix dimensions do not match");
+		let mut m = Matrix::zeros(self.rows, rhs.cols);
+		for i in 0..self.rows {
+			for k in 0..self.cols {
+				let a = self[(i, k)];
+				for j in 0..rhs.cols {
+					m[(i, j)] += a * rhs[(k, j)];
+				}
+			}
+		}
+		m
+	}
+}
+
+/// Computes the Jacobian of `f` at `x` with central differences.
+pub(crate) fn central_difference_jacobian<F>(mut f: F, x: &[f64]) -> Matrix
+where
+	F: FnMut(&[f64]) -> Vec<f64>,
+{
+	// roughly the cube root of machine epsilon, which balances truncation and round-off error
+	const RELATIVE_STEP: f64 = 6e-6;
+
+	let columns: Vec<Vec<f64>> = (0..x.len())
+		.map(|j| {
+			let delta = RELATIVE_STEP * x[j].abs().max(1.0);
+			let mut x_perturbed = x.to_vec();
+
+			x_perturbed[j] = x[j] + delta;
+			let f_plus = f(&x_perturbed);
+			x_perturbed[j] = x[j] - delta;
+			let f_minus = f(&x_perturbed);
+
+			f_plus
+				.iter()
+				.zip(&f_minus)
+				.map(|(a, b)| (a - b) / (2.0 * delta))
+				.collect()
+		})
+		.collect();
+
+	Matrix::from_columns(&columns)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn sorted_by_real_part(mut eigenvalues: Vec<Complex>) -> Vec<Complex> {
+		eigenvalues.sort_by(|a, b| a.re.total_cmp(&b.re).then(a.im.total_cmp(&b.im)));
+		eigenvalues
+	}
+
+	#[test]
+	fn solves_linear_system() {
+		let a = Matrix::from_row_major(3, 3, vec![0.0, 1.0, 0.0, 0.0, 0.0, 1.0, -6.0, -11.0, -6.0]);
+		let x = a.solve(&[1.0, 2.0, 3.0]).unwrap();
+		for (actual, expected) in x.iter().zip([-13.0 / 3.0, 1.0, 2.0]) {
+			assert!((actual - expected).abs() < 1e-12);
+		}
+
+		let singular = Matrix::from_row_major(2, 2, vec![1.0, 2.0, 2.0, 4.0]);
+		assert_eq!(singular.solve(&[1.0, 1.0]), None);
+	}
+
+	#[test]
+	fn finds_real_and_complex_eigenvalues() {
+		// companion matrix of (s + 1)(s + 2)(s + 3)
+		let a = Matrix::from_row_major(3, 3, vec![0.0, 1.0, 0.0, 0.0, 0.0, 1.0, -6.0, -11.0, -6.0]);
+		let eigenvalues = sorted_by_real_part(a.eigenvalues().unwrap());
+		for (actual, expected) in eigenvalues.iter().zip([-3.0, -2.0, -1.0]) {
+			assert!((actual.re - expected).abs() < 1e-9 && actual.im == 0.0);
+		}
+
+		// damped oscillator with a natural frequency of 2 and a damping ratio of 0.1
+		let a = Matrix::from_row_major(2, 2, vec![0.0, 1.0, -4.0, -0.4]);
+		let eigenvalues = sorted_by_real_part(a.eigenvalues().unwrap());
+		assert!((eigenvalues[0].im + f64::sqrt(3.96)).abs() < 1e-12);
+		assert!((eigenvalues[1].natural_frequency() - 2.0).abs() < 1e-12);
+		assert!((eigenvalues[1].damping_ratio() - 0.1).abs() < 1e-12);
+	}
+}
@@ -0,0 +1,385 @@
+//! Trim and linearization of an integration group's dynamics.
+
+use std::{error::Error, fmt};
+
+use crate::{
+	SimTime,
+	integrator::{Deferred, IntegrationGroup, StateLoader, StateUnloader},
+	linalg::{Complex, Matrix, central_difference_jacobian},
+};
+
+/// The control inputs of a model, which are held constant while its derivative is evaluated.
+pub struct Inputs<S> {
+	loader: StateLoader<S>,
+	unloader: StateUnloader<S>,
+}
+
+impl<S> Inputs<S> {
+	pub fn new<L, U>(input_loader: L, input_unloader: U) -> Self
+	where
+		L: Fn(&S, &SimTime) -> Vec<f64> + 'static,
+		U: FnMut(&mut S, &[f64]) + 'static,
+	{
+		Self {
+			loader: Box::new(input_loader),
+			unloader: Box::new(input_unloader),
+		}
+	}
+
+	/// A model with no inputs.
+	pub fn none() -> Self {
+		Self::new(|_, _| Vec::new(), |_, _| {})
+	}
+}
+
+/// A linear model `x' = f(x0, u0) + A (x - x0) + B (u - u0)` about an operating point.
+#[derive(Clone, Debug)]
+pub struct Linearization {
+	pub a: Matrix,
+	pub b: Matrix,
+	pub state: Vec<f64>,
+	pub input: Vec<f64>,
+	/// The derivative at the operating point, which is zero at an equilibrium.
+	pub derivative: Vec<f64>,
+}
+
+impl Linearization {
+	/// Returns the eigenvalues of `A`, or `None` if they could not be computed.
+	pub fn eigenvalues(&self) -> Option<Vec<Complex>> {
+		self.a.eigenvalues()
+	}
+
+	/// Returns true if every eigenvalue of `A` has a negative real part.
+	pub fn is_stable(&self) -> bool {
+		self.eigenvalues()
+			.is_some_and(|eigenvalues| eigenvalues.iter().all(|lambda| lambda.re < 0.0))
+	}
+}
+
+/// Settings for finding a trim (equilibrium) point with Newton's method.
+///
+/// The free states and inputs are adjusted until the target derivatives are zero. There must be as
+/// many targets as free variables.
+#[derive(Clone, Debug)]
+pub struct Trim {
+	free_states: Vec<usize>,
+	free_inputs: Vec<usize>,
+	targets: Vec<usize>,
+	tolerance: f64,
+	max_iterations: usize,
+}
+
+impl Default for Trim {
+	fn default() -> Self {
+		Self {
+			free_states: Vec::new(),
+			free_inputs: Vec::new(),
+			targets: Vec::new(),
+			tolerance: 1e-9,
+			max_iterations: 50,
+		}
+	}
+}
+
+impl Trim {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the indices of the states that the solver may change.
+	pub fn free_states(mut self, indices: &[usize]) -> Self {
+		self.free_states = indices.to_vec();
+		self
+	}
+
+	/// Sets the indices of the inputs that the solver may change.
+	pub fn free_inputs(mut self, indices: &[usize]) -> Self {
+		self.free_inputs = indices.to_vec();
+		self
+	}
+
+	/// Sets the indices of the derivatives that must be driven to zero.
+	pub fn targets(mut self, indices: &[usize]) -> Self {
+		self.targets = indices.to_vec();
+		self
+	}
+
+	/// Sets the largest absolute target derivative accepted as trimmed.
+	pub fn tolerance(mut self, tolerance: f64) -> Self {
+		self.tolerance = tolerance;
+		self
+	}
+
+	pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+		self.max_iterations = max_iterations;
+		self
+	}
+}
+
+/// A trimmed operating point.
+#[derive(Clone, Debug)]
+pub struct TrimResult<S> {
+	/// The simulation with the trimmed states and inputs.
+	pub sim: S,
+	pub iterations: usize,
+	/// The largest absolute target derivative at the trim point.
+	pub residual: f64,
+	/// The linearization about the trim point.
+	pub linearization: Linearization,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TrimError {
+	/// The number of free variables does not match the number of targets.
+	NotSquare { free: usize, targets: usize },
+	/// The Jacobian of the targets became singular.
+	Singular { iteration: usize },
+	/// The targets were not driven below the tolerance in the allowed number of iterations.
+	NotConverged { iterations: usize, residual: f64 },
+}
+
+impl fmt::Display for TrimError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::NotSquare { free, targets } => {
+				write!(f, "trim has {free} free variables but {targets} targets")
+			},
+			Self::Singular { iteration } => {
+				write!(f, "trim Jacobian became singular on iteration {iteration}")
+			},
+			Self::NotConverged {
+				iterations,
+				residual,
+			} => write!(
+				f,
+				"trim did not converge after {iterations} iterations (residual {residual})"
+			),
+		}
+	}
+}
+
+impl Error for TrimError {}
+
+/// Evaluates a group's derivative with the given state and input, discarding discrete updates.
+fn evaluate<S>(
+	group: &mut IntegrationGroup<S>,
+	inputs: &mut Inputs<S>,
+	sim: &mut S,
+	time: &SimTime,
+	x: &[f64],
+	u: &[f64],
+) -> Vec<f64> {
+	(group.state_unloader)(sim, x);
+	(inputs.unloader)(sim, u);
+	(group.derivative)(sim, time, &mut Deferred::new())
+}
+
+pub(crate) fn linearize<S: Clone>(
+	group: &mut IntegrationGroup<S>,
+	inputs: &mut Inputs<S>,
+	sim: &S,
+	time: &SimTime,
+) -> Linearization {
+	let mut scratch = sim.clone();
+	let x0 = (group.state_loader)(sim, time);
+	let u0 = (inputs.loader)(sim, time);
+
+	let derivative = evaluate(group, inputs, &mut scratch, time, &x0, &u0);
+	let a =
+		central_difference_jacobian(|x| evaluate(group, inputs, &mut scratch, time, x, &u0), &x0);
+	let b = if u0.is_empty() {
+		Matrix::zeros(x0.len(), 0)
+	} else {
+		central_difference_jacobian(|u| evaluate(group, inputs, &mut scratch, time, &x0, u), &u0)
+	};
+
+	Linearization {
+		a,
+		b,
+		state: x0,
+		input: u0,
+		derivative,
+	}
+}
+
+pub(crate) fn trim<S: Clone>(
+	group: &mut IntegrationGroup<S>,
+	inputs: &mut Inputs<S>,
+	sim: &S,
+	time: &SimTime,
+	settings: &Trim,
+) -> Result<TrimResult<S>, TrimError> {
+	let free = settings.free_states.len() + settings.free_inputs.len();
+	if free != settings.targets.len() {
+		return Err(TrimError::NotSquare {
+			free,
+			targets: settings.targets.len(),
+		});
+	}
+
+	let mut scratch = sim.clone();
+	let mut x = (group.state_loader)(sim, time);
+	let mut u = (inputs.loader)(sim, time);
+
+	// the free variables are the free states followed by the free inputs
+	let pack = |x: &[f64], u: &[f64]| -> Vec<f64> {
+		let states = settings.free_states.iter().map(|&i| x[i]);
+		let inputs = settings.free_inputs.iter().map(|&i| u[i]);
+		states.chain(inputs).collect()
+	};
+	let unpack = |z: &[f64], x: &mut [f64], u: &mut [f64]| {
+		let (states, inputs) = z.split_at(settings.free_states.len());
+		for (&i, value) in settings.free_states.iter().zip(states) {
+			x[i] = *value;
+		}
+		for (&i, value) in settings.free_inputs.iter().zip(inputs) {
+			u[i] = *value;
+		}
+	};
+	let max_abs = |values: &[f64]| values.iter().fold(0.0, |max: f64, v| max.max(v.abs()));
+
+	let mut residuals = |z: &[f64], x: &mut Vec<f64>, u: &mut Vec<f64>| -> Vec<f64> {
+		unpack(z, x, u);
+		let derivative = evaluate(group, inputs, &mut scratch, time, x, u);
+		settings.targets.iter().map(|&i| derivative[i]).collect()
+	};
+
+	let mut z = pack(&x, &u);
+	let mut iterations = 0;
+	loop {
+		let r = residuals(&z, &mut x, &mut u);
+		let residual = max_abs(&r);
+		if residual <= settings.tolerance {
+			break;
+		}
+		if iterations == settings.max_iterations {
+			return Err(TrimError::NotConverged {
+				iterations,
+				residual,
+			});
+		}
+		iterations += 1;
+
+		let (mut x_fd, mut u_fd) = (x.clone(), u.clone());
+		let jacobian = central_difference_jacobian(|z| residuals(z, &mut x_fd, &mut u_fd), &z);
+		let negative_r: Vec<f64> = r.iter().map(|r| -r).collect();
+		let step = jacobian.solve(&negative_r).ok_or(TrimError::Singular {
+			iteration: iterations,
+		})?;
+
+		z.iter_mut().zip(&step).for_each(|(z, dz)| *z += dz);
+	}
+
+	let residual = max_abs(&residuals(&z, &mut x, &mut u));
+
+	let mut trimmed = sim.clone();
+	(group.state_unloader)(&mut trimmed, &x);
+	(inputs.unloader)(&mut trimmed, &u);
+	let linearization = linearize(group, inputs, &trimmed, time);
+
+	Ok(TrimResult {
+		sim: trimmed,
+		iterations,
+		residual,
+		linearization,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Executor;
+
+	const G_OVER_L: f64 = 9.81;
+	const DAMPING: f64 = 0.4;
+
+	#[derive(Clone, Debug, Default)]
+	struct Pendulum {
+		theta: f64,
+		omega: f64,
+		torque: f64,
+	}
+
+	/// A damped pendulum, `theta'' = -(g / L) sin(theta) - c theta' + torque`.
+	fn pendulum() -> (Executor<Pendulum>, Inputs<Pendulum>) {
+		let mut exec = Executor::new(0.01, 1.0);
+		exec.add_integration_group(IntegrationGroup::new(
+			"pendulum",
+			|s: &Pendulum, _| vec![s.theta, s.omega],
+			|s, _, _| {
+				let alpha = -G_OVER_L * s.theta.sin() - DAMPING * s.omega + s.torque;
+				vec![s.omega, alpha]
+			},
+			|s, y| (s.theta, s.omega) = (y[0], y[1]),
+		));
+		let inputs = Inputs::new(|s: &Pendulum, _| vec![s.torque], |s, u| s.torque = u[0]);
+		(exec, inputs)
+	}
+
+	fn assert_close(actual: &Matrix, expected: &[f64]) {
+		for (a, e) in actual.as_slice().iter().zip(expected) {
+			assert!((a - e).abs() < 1e-6, "{:?} != {expected:?}", actual.as_slice());
+		}
+	}
+
+	#[test]
+	fn linearizes_about_any_point() {
+		let (mut exec, mut inputs) = pendulum();
+		let sim = Pendulum {
+			theta: 1.0,
+			omega: 0.5,
+			torque: 2.0,
+		};
+
+		let linearization = exec.linearize("pendulum", &sim, &mut inputs);
+		assert_close(&linearization.a, &[0.0, 1.0, -G_OVER_L * f64::cos(1.0), -DAMPING]);
+		assert_close(&linearization.b, &[0.0, 1.0]);
+		let alpha = -G_OVER_L * f64::sin(1.0) - DAMPING * 0.5 + 2.0;
+		assert_eq!(linearization.derivative, [0.5, alpha]);
+		assert_eq!((linearization.state, linearization.input), (vec![1.0, 0.5], vec![2.0]));
+	}
+
+	#[test]
+	fn trims_to_the_equilibrium_and_linearizes_there() {
+		// held at theta0, the pendulum is at rest when the torque balances gravity
+		let theta0: f64 = 0.3;
+		let (mut exec, mut inputs) = pendulum();
+		let sim = Pendulum {
+			theta: theta0,
+			omega: 0.5,
+			torque: 0.0,
+		};
+		let settings = Trim::new()
+			.free_states(&[1])
+			.free_inputs(&[0])
+			.targets(&[0, 1]);
+
+		let trimmed = exec.trim("pendulum", &sim, &mut inputs, &settings).unwrap();
+		assert!(trimmed.residual <= 1e-9);
+		assert_eq!(trimmed.sim.theta, theta0);
+		assert!(trimmed.sim.omega.abs() < 1e-9);
+		assert!((trimmed.sim.torque - G_OVER_L * theta0.sin()).abs() < 1e-9);
+
+		let linearization = &trimmed.linearization;
+		assert_close(&linearization.a, &[0.0, 1.0, -G_OVER_L * theta0.cos(), -DAMPING]);
+		assert_close(&linearization.b, &[0.0, 1.0]);
+		assert!(linearization.derivative.iter().all(|d| d.abs() < 1e-9));
+		assert!(linearization.is_stable());
+	}
+
+	#[test]
+	fn trim_needs_as_many_free_variables_as_targets() {
+		let (mut exec, mut inputs) = pendulum();
+		let settings = Trim::new().free_inputs(&[0]).targets(&[0, 1]);
+		let error = exec
+			.trim("pendulum", &Pendulum::default(), &mut inputs, &settings)
+			.unwrap_err();
+		assert_eq!(
+			error,
+			TrimError::NotSquare {
+				free: 1,
+				targets: 2
+			}
+		);
+	}
+}
@@ -0,0 +1,381 @@
+//! Summary metrics reduced from a run as it goes, without keeping its time histories.
+
+use std::fmt;
+
+use crate::events::Event;
+
+/// How a metric reduces the values of its accessor over a run.
+///
+/// Metrics are evaluated after the Init phase and at the end of every frame. The extremes and their
+/// times leave out NaN values, so they are `None` only if every value was NaN.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reduction {
+	Max,
+	Min,
+	/// The value at the last evaluation.
+	Final,
+	/// The time of the largest value.
+	TimeOfMax,
+	/// The time of the smallest value.
+	TimeOfMin,
+	/// The trapezoidal integral over time.
+	Integral,
+	/// The value and time at the end of the frame in which the named event was first emitted.
+	AtEvent(String),
+}
+
+/// A metric registered with [`Executor::add_metric`](crate::Executor::add_metric).
+pub struct Metric<S> {
+	name: String,
+	unit: String,
+	reduction: Reduction,
+	accessor: Box<dyn Fn(&S) -> f64>,
+	// the value and time the reduction has kept, and the last sample for integrals
+	kept: Option<(f64, f64)>,
+	last: Option<(f64, f64)>,
+	integral: f64,
+}
+
+impl<S> Metric<S> {
+	pub(crate) fn new<F>(name: &str, reduction: Reduction, accessor: F) -> Self
+	where
+		F: Fn(&S) -> f64 + 'static,
+	{
+		Self {
+			name: name.to_string(),
+			unit: String::new(),
+			reduction,
+			accessor: Box::new(accessor),
+			kept: None,
+			last: None,
+			integral: 0.0,
+		}
+	}
+
+	/// Sets the unit of the accessor's values.
+	///
+	/// The summary reports times in `s` and integrals in the unit multiplied by `s`.
+	pub fn unit(&mut self, unit: &str) -> &mut Self {
+		self.unit = unit.to_string();
+		self
+	}
+
+	pub(crate) fn reset(&mut self) {
+		self.kept = None;
+		self.last = None;
+		self.integral = 0.0;
+	}
+
+	/// Updates the reduction with the simulation at time `t`, given the events emitted since the
+	/// last update.
+	pub(crate) fn update(&mut self, sim: &S, t: f64, events: &[Event]) {
+		let x = (self.accessor)(sim);
+		match &self.reduction {
+			Reduction::Max | Reduction::TimeOfMax => {
+				if !x.is_nan() && self.kept.is_none_or(|(max, _)| x > max) {
+					self.kept = Some((x, t));
+				}
+			},
+			Reduction::Min | Reduction::TimeOfMin => {
+				if !x.is_nan() && self.kept.is_none_or(|(min, _)| x < min) {
+					self.kept = Some((x, t));
+				}
+			},
+			Reduction::Final => self.kept = Some((x, t)),
+			Reduction::Integral => {
+				if let Some((x0, t0)) = self.last {
+					self.integral += 0.5 * (x0 + x) * (t - t0);
+				}
+			},
+			Reduction::AtEvent(name) => {
+				if self.kept.is_none() && events.iter().any(|event| &event.name == name) {
+					self.kept = Some((x, t));
+				}
+			},
+		}
+		self.last = Some((x, t));
+	}
+
+	pub(crate) fn value(&self) -> MetricValue {
+		let (value, unit) = match self.reduction {
+			Reduction::TimeOfMax | Reduction::TimeOfMin => {
+				(self.kept.map(|(_, t)| t), "s".to_string())
+			},
+			Reduction::Integral => {
+				let unit = match self.unit.as_str() {
+					"" => "s".to_string(),
+					unit => format!("{unit}*s"),
+				};
+				(self.last.map(|_| self.integral), unit)
+			},
+			_ => (self.kept.map(|(x, _)| x), self.unit.clone()),
+		};
+		let t = match self.reduction {
+			Reduction::Integral => self.last.map(|(_, t)| t),
+			_ => self.kept.map(|(_, t)| t),
+		};
+		MetricValue {
+			name: self.name.clone(),
+			unit,
+			value,
+			t,
+		}
+	}
+}
+
+/// The result of one metric.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricValue {
+	pub name: String,
+	pub unit: String,
+	/// The reduced value, or `None` if the metric was never evaluated or its event never happened.
+	pub value: Option<f64>,
+	/// The time the value was taken at, which is the end of the run for integrals.
+	pub t: Option<f64>,
+}
+
+/// The metrics of a run, in the order they were added.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Summary {
+	pub metrics: Vec<MetricValue>,
+}
+
+impl Summary {
+	pub fn metric(&self, name: &str) -> Option<&MetricValue> {
+		self.metrics.iter().find(|metric| metric.name == name)
+	}
+
+	/// Returns the value of a metric.
+	pub fn get(&self, name: &str) -> Option<f64> {
+		self.metric(name).and_then(|metric| metric.value)
+	}
+}
+
+impl fmt::Display for Summary {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{:<24} {:>14} {:<10} {:>10}", "metric", "value", "unit", "at")?;
+		let number = |x: Option<f64>| x.map_or("-".to_string(), |x| format!("{x:.6}"));
+		for metric in &self.metrics {
+			write!(
+				f,
+				"\n{:<24} {:>14} {:<10} {:>10}",
+				metric.name,
+				number(metric.value),
+				metric.unit,
+				number(metric.t),
+			)?;
+		}
+		Ok(())
+	}
+}
+
+/// Statistics of one metric across the cases of a [`SummaryTable`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricStatistics {
+	pub name: String,
+	pub unit: String,
+	/// The number of cases that have a value for the metric.
+	pub count: usize,
+	pub min: f64,
+	pub max: f64,
+	pub mean: f64,
+	/// The sample standard deviation, which is zero for a single case.
+	pub std_dev: f64,
+}
+
+/// The summaries of several runs, such as the cases of a Monte Carlo or parameter sweep.
+#[derive(Clone, Debug, Default)]
+pub struct SummaryTable {
+	cases: Vec<(String, Summary)>,
+}
+
+impl SummaryTable {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn push(&mut self, case: &str, summary: Summary) {
+		self.cases.push((case.to_string(), summary));
+	}
+
+	pub fn cases(&self) -> &[(String, Summary)] {
+		&self.cases
+	}
+
+	/// Returns the values of a metric in each case.
+	pub fn values(&self, name: &str) -> Vec<Option<f64>> {
+		self.cases
+			.iter()
+			.map(|(_, summary)| summary.get(name))
+			.collect()
+	}
+
+	/// Returns statistics of every metric, in the order they first appear in the cases.
+	///
+	/// Cases without a value for a metric are left out of its statistics.
+	pub fn statistics(&self) -> Vec<MetricStatistics> {
+		self.metric_names()
+			.into_iter()
+			.map(|(name, unit)| {
+				let values: Vec<f64> = self.values(&name).into_iter().flatten().collect();
+				let count = values.len();
+				let mean = values.iter().sum::<f64>() / count as f64;
+				let variance = match count {
+					0 | 1 => 0.0,
+					_ => {
+						values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (count - 1) as f64
+					},
+				};
+				MetricStatistics {
+					name,
+					unit,
+					count,
+					min: values.iter().copied().fold(f64::NAN, f64::min),
+					max: values.iter().copied().fold(f64::NAN, f64::max),
+					mean,
+					std_dev: variance.sqrt(),
+				}
+			})
+			.collect()
+	}
+
+	fn metric_names(&self) -> Vec<(String, String)> {
+		let mut names: Vec<(String, String)> = Vec::new();
+		for (_, summary) in &self.cases {
+			for metric in &summary.metrics {
+				if !names.iter().any(|(name, _)| name == &metric.name) {
+					names.push((metric.name.clone(), metric.unit.clone()));
+				}
+			}
+		}
+		names
+	}
+}
+
+impl fmt::Display for SummaryTable {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let names = self.metric_names();
+		write!(f, "{:<16}", "case")?;
+		for (name, _) in &names {
+			write!(f, " {name:>14}")?;
+		}
+		let number = |x: Option<f64>| x.map_or("-".to_string(), |x| format!("{x:.6}"));
+		for (case, summary) in &self.cases {
+			write!(f, "\n{case:<16}")?;
+			for (name, _) in &names {
+				write!(f, " {:>14}", number(summary.get(name)))?;
+			}
+		}
+
+		let statistics = self.statistics();
+		type Column = fn(&MetricStatistics) -> f64;
+		let rows: [(&str, Column); 4] = [
+			("min", |s| s.min),
+			("max", |s| s.max),
+			("mean", |s| s.mean),
+			("std dev", |s| s.std_dev),
+		];
+		for (label, column) in rows {
+			write!(f, "\n{label:<16}")?;
+			for statistics in &statistics {
+				let value = Some(column(statistics)).filter(|_| statistics.count > 0);
+				write!(f, " {:>14}", number(value))?;
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Executor, Phase};
+
+	#[test]
+	fn reductions_over_a_run() {
+		let mut exec = Executor::<f64>::new(0.25, 2.0);
+		exec.set_integrator(|x, _| vec![*x], |_, _, _| vec![1.0], |x, y| *x = y[0]);
+		let log = exec.event_log();
+		exec.add_job(Phase::PostIntegrate, move |x, _| {
+			if *x == 0.5 {
+				log.info("Half", &[]);
+			}
+		});
+
+		let parabola = |x: &f64| x * (2.0 - x);
+		exec.add_metric("peak", Reduction::Max, parabola).unit("m");
+		exec.add_metric("peak_time", Reduction::TimeOfMax, parabola);
+		exec.add_metric("lowest", Reduction::Min, parabola);
+		exec.add_metric("final", Reduction::Final, |x| *x);
+		exec.add_metric("area", Reduction::Integral, |x| *x)
+			.unit("m");
+		exec.add_metric("half", Reduction::AtEvent("Half".into()), |x| *x);
+		exec.add_metric("never", Reduction::AtEvent("Never".into()), |x| *x);
+
+		let summary = exec.run(0.0).summary;
+		assert_eq!(summary.get("peak"), Some(1.0));
+		assert_eq!(summary.get("peak_time"), Some(1.0));
+		assert_eq!(summary.metric("peak_time").unwrap().unit, "s");
+		assert_eq!(summary.metric("lowest").unwrap().t, Some(0.0));
+		assert_eq!(summary.get("final"), Some(2.0));
+		assert_eq!(summary.get("area"), Some(2.0));
+		assert_eq!(summary.metric("area").unwrap().unit, "m*s");
+		let half = summary.metric("half").unwrap();
+		assert_eq!((half.value, half.t), (Some(0.5), Some(0.5)));
+		assert_eq!(summary.get("never"), None);
+	}
+
+	#[test]
+	fn extremes_leave_out_nan() {
+		let mut exec = Executor::<f64>::new(0.5, 2.0);
+		exec.set_integrator(|x, _| vec![*x], |_, _, _| vec![1.0], |x, y| *x = y[0]);
+		// NaN at the first evaluation and halfway through
+		let value = |x: &f64| {
+			if *x == 0.0 || *x == 1.0 {
+				f64::NAN
+			} else {
+				x * (2.0 - x)
+			}
+		};
+		exec.add_metric("peak", Reduction::Max, value);
+		exec.add_metric("peak_time", Reduction::TimeOfMax, value);
+		exec.add_metric("lowest", Reduction::Min, value);
+		exec.add_metric("lowest_time", Reduction::TimeOfMin, value);
+		exec.add_metric("never", Reduction::Max, |_| f64::NAN);
+
+		let summary = exec.run(0.0).summary;
+		assert_eq!(summary.get("peak"), Some(0.75));
+		assert_eq!(summary.get("peak_time"), Some(0.5));
+		assert_eq!(summary.get("lowest"), Some(0.0));
+		assert_eq!(summary.get("lowest_time"), Some(2.0));
+		assert_eq!(summary.get("never"), None);
+	}
+
+	#[test]
+	fn statistics_across_cases() {
+		let mut table = SummaryTable::new();
+		for (case, value) in [("a", Some(1.0)), ("b", Some(3.0)), ("c", None)] {
+			let metric = MetricValue {
+				name: "x".into(),
+				unit: "m".into(),
+				value,
+				t: None,
+			};
+			table.push(
+				case,
+				Summary {
+					metrics: vec![metric],
+				},
+			);
+		}
+
+		let x = &table.statistics()[0];
+		assert_eq!((x.count, x.min, x.max, x.mean), (2, 1.0, 3.0, 2.0));
+		assert!((x.std_dev - 2f64.sqrt()).abs() < 1e-12);
+		assert!(
+			table
+				.to_string()
+				.contains("c                             -")
+		);
+	}
+}
@@ -0,0 +1,539 @@
+//! Quick-look plots of recorded runs, rendered to SVG.
+//!
+//! Plots are drawn from [`RunData`], whether loaded from a file or kept in memory by a recorder and
+//! returned in [`RunResult::recordings`](crate::RunResult::recordings).
+
+use std::{fmt::Write as _, fs, io, path::Path};
+
+use crate::{RunData, events::Event};
+
+// a colorblind-friendly palette, cycled through by the series that have their own color
+const COLORS: [&str; 8] = [
+	"#0072b2", "#d55e00", "#009e73", "#cc79a7", "#e69f00", "#56b4e9", "#f0e442", "#000000",
+];
+
+const MARGIN_LEFT: f64 = 80.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 40.0;
+const MARGIN_BOTTOM: f64 = 50.0;
+
+struct Series {
+	/// The name shown in the legend, or `None` to leave the series out of it.
+	legend: Option<String>,
+	points: Vec<(f64, f64)>,
+	color: usize,
+	opacity: f64,
+	x_label: String,
+	y_label: String,
+}
+
+/// A line plot of time histories or of one channel against another.
+///
+/// Axis labels default to the channels' names and units from the recording's metadata. A series
+/// is broken wherever a value is NaN or infinite.
+pub struct Plot {
+	title: String,
+	width: f64,
+	height: f64,
+	x_label: Option<String>,
+	y_label: Option<String>,
+	series: Vec<Series>,
+	events: Vec<Event>,
+}
+
+impl Plot {
+	/// Creates an empty 800 by 500 plot.
+	pub fn new(title: &str) -> Self {
+		Self {
+			title: title.to_string(),
+			width: 800.0,
+			height: 500.0,
+			x_label: None,
+			y_label: None,
+			series: Vec::new(),
+			events: Vec::new(),
+		}
+	}
+
+	/// Creates a plot of channels against time, straight from a recorder's in-memory samples or any
+	/// other run.
+	///
+	/// # Panics
+	///
+	/// Panics if the run is missing any of the numeric channels.
+	pub fn time_histories(title: &str, run: &RunData, channels: &[&str]) -> Self {
+		channels
+			.iter()
+			.fold(Self::new(title), |plot, channel| plot.time_history(run, channel))
+	}
+
+	pub fn size(mut self, width: u32, height: u32) -> Self {
+		self.width = f64::from(width);
+		self.height = f64::from(height);
+		self
+	}
+
+	pub fn x_label(mut self, label: &str) -> Self {
+		self.x_label = Some(label.to_string());
+		self
+	}
+
+	pub fn y_label(mut self, label: &str) -> Self {
+		self.y_label = Some(label.to_string());
+		self
+	}
+
+	/// Adds a channel of a run against time.
+	///
+	/// # Panics
+	///
+	/// Panics if the run has no numeric channel named `channel`.
+	pub fn time_history(mut self, run: &RunData, channel: &str) -> Self {
+		let points = run
+			.time()
+			.iter()
+			.copied()
+			.zip(column(run, channel))
+			.collect();
+		let color = self.next_color();
+		self.series.push(Series {
+			legend: Some(channel.to_string()),
+			points,
+			color,
+			opacity: 1.0,
+			x_label: "t [s]".to_string(),
+			y_label: run.label(channel).unwrap(),
+		});
+		self
+	}
+
+	/// Adds channel `y` of a run against channel `x`, such as a trajectory.
+	///
+	/// # Panics
+	///
+	/// Panics if the run is missing either numeric channel.
+	pub fn xy(mut self, run: &RunData, x: &str, y: &str) -> Self {
+		let points = column(run, x).zip(column(run, y)).collect();
+		let color = self.next_color();
+		self.series.push(Series {
+			legend: Some(format!("{y} vs {x}")),
+			points,
+			color,
+			opacity: 1.0,
+			x_label: run.label(x).unwrap(),
+			y_label: run.label(y).unwrap(),
+		});
+		self
+	}
+
+	/// Adds a series of points that did not come from a recording.
+	pub fn series(mut self, label: &str, points: &[(f64, f64)]) -> Self {
+		let color = self.next_color();
+		self.series.push(Series {
+			legend: Some(label.to_string()),
+			points: points.to_vec(),
+			color,
+			opacity: 1.0,
+			x_label: String::new(),
+			y_label: String::new(),
+		});
+		self
+	}
+
+	/// Adds the same channel of several runs against time, such as the cases of a Monte Carlo.
+	///
+	/// The runs are drawn in one translucent color and share a single legend entry.
+	///
+	/// # Panics
+	///
+	/// Panics if any run has no numeric channel named `channel`.
+	pub fn overlay(mut self, runs: &[RunData], channel: &str) -> Self {
+		let color = self.next_color();
+		for (i, run) in runs.iter().enumerate() {
+			let points = run
+				.time()
+				.iter()
+				.copied()
+				.zip(column(run, channel))
+				.collect();
+			self.series.push(Series {
+				legend: (i == 0).then(|| format!("{channel} ({} runs)", runs.len())),
+				points,
+				color,
+				opacity: 0.4,
+				x_label: "t [s]".to_string(),
+				y_label: run.label(channel).unwrap(),
+			});
+		}
+		self
+	}
+
+	/// Marks the times of events with labelled vertical lines.
+	///
+	/// Only events within the range of the x axis are drawn, so this is meant for plots against
+	/// time.
+	pub fn events(mut self, events: &[Event]) -> Self {
+		self.events.extend_from_slice(events);
+		self
+	}
+
+	pub fn to_svg(&self) -> String {
+		let (x_range, y_range) = self.ranges();
+		let x_ticks = ticks(x_range);
+		let y_ticks = ticks(y_range);
+		// the axes extend to whole ticks around the data
+		let x_range = (x_ticks.start, x_ticks.end);
+		let y_range = (y_ticks.start, y_ticks.end);
+
+		let left = MARGIN_LEFT;
+		let right = self.width - MARGIN_RIGHT;
+		let top = MARGIN_TOP;
+		let bottom = self.height - MARGIN_BOTTOM;
+		let px = |x: f64| left + (x - x_range.0) / (x_range.1 - x_range.0) * (right - left);
+		let py = |y: f64| bottom - (y - y_range.0) / (y_range.1 - y_range.0) * (bottom - top);
+
+		let mut svg = String::new();
+		let (width, height) = (self.width, self.height);
+		writeln!(
+			svg,
+			"<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+			 viewBox=\"0 0 {width} {height}\" font-family=\"sans-serif\" font-size=\"12\">"
+		)
+		.unwrap();
+		writeln!(svg, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>").unwrap();
+		writeln!(
+			svg,
+			"<text x=\"{:.1}\" y=\"24\" text-anchor=\"middle\" font-size=\"16\">{}</text>",
+			(left + right) / 2.0,
+			escape(&self.title)
+		)
+		.unwrap();
+
+		// grid and tick labels
+		for x in x_ticks.values() {
+			let x_px = px(x);
+			writeln!(
+				svg,
+				"<line x1=\"{x_px:.1}\" y1=\"{top:.1}\" x2=\"{x_px:.1}\" y2=\"{bottom:.1}\" \
+				 stroke=\"#e0e0e0\"/>"
+			)
+			.unwrap();
+			writeln!(
+				svg,
+				"<text x=\"{x_px:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+				bottom + 16.0,
+				x_ticks.format(x)
+			)
+			.unwrap();
+		}
+		for y in y_ticks.values() {
+			let y_px = py(y);
+			writeln!(
+				svg,
+				"<line x1=\"{left:.1}\" y1=\"{y_px:.1}\" x2=\"{right:.1}\" y2=\"{y_px:.1}\" \
+				 stroke=\"#e0e0e0\"/>"
+			)
+			.unwrap();
+			writeln!(
+				svg,
+				"<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>",
+				left - 6.0,
+				y_px + 4.0,
+				y_ticks.format(y)
+			)
+			.unwrap();
+		}
+		writeln!(
+			svg,
+			"<rect x=\"{left:.1}\" y=\"{top:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"none\" \
+			 stroke=\"black\"/>",
+			right - left,
+			bottom - top
+		)
+		.unwrap();
+
+		// axis labels
+		writeln!(
+			svg,
+			"<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+			(left + right) / 2.0,
+			height - 12.0,
+			escape(&self.axis_label(self.x_label.as_deref(), |s| &s.x_label))
+		)
+		.unwrap();
+		let y_mid = (top + bottom) / 2.0;
+		writeln!(
+			svg,
+			"<text x=\"18\" y=\"{y_mid:.1}\" text-anchor=\"middle\" \
+			 transform=\"rotate(-90 18 {y_mid:.1})\">{}</text>",
+			escape(&self.axis_label(self.y_label.as_deref(), |s| &s.y_label))
+		)
+		.unwrap();
+
+		for event in &self.events {
+			if event.t < x_range.0 || event.t > x_range.1 {
+				continue;
+			}
+			let x_px = px(event.t);
+			writeln!(
+				svg,
+				"<line x1=\"{x_px:.1}\" y1=\"{top:.1}\" x2=\"{x_px:.1}\" y2=\"{bottom:.1}\" \
+				 stroke=\"#808080\" stroke-dasharray=\"4 3\"/>"
+			)
+			.unwrap();
+			let y_px = top + 6.0;
+			writeln!(
+				svg,
+				"<text x=\"{:.1}\" y=\"{y_px:.1}\" transform=\"rotate(90 {:.1} {y_px:.1})\" \
+				 fill=\"#606060\">{}</text>",
+				x_px + 4.0,
+				x_px + 4.0,
+				escape(&event.name)
+			)
+			.unwrap();
+		}
+
+		for series in &self.series {
+			let color = COLORS[series.color % COLORS.len()];
+			for segment in series
+				.points
+				.split(|(x, y)| !x.is_finite() || !y.is_finite())
+			{
+				if segment.is_empty() {
+					continue;
+				}
+				let points: Vec<String> = segment
+					.iter()
+					.map(|&(x, y)| format!("{:.2},{:.2}", px(x), py(y)))
+					.collect();
+				writeln!(
+					svg,
+					"<polyline points=\"{}\" fill=\"none\" stroke=\"{color}\" stroke-width=\"1.5\" \
+					 stroke-opacity=\"{}\"/>",
+					points.join(" "),
+					series.opacity
+				)
+				.unwrap();
+			}
+		}
+
+		// legend in the top right corner of the plot area
+		let legend: Vec<(&str, &str)> = self
+			.series
+			.iter()
+			.filter_map(|s| Some((s.legend.as_deref()?, COLORS[s.color % COLORS.len()])))
+			.collect();
+		if !legend.is_empty() {
+			let longest = legend.iter().map(|(label, _)| label.chars().count()).max();
+			let box_width = 36.0 + 7.0 * longest.unwrap() as f64;
+			let box_left = right - box_width - 8.0;
+			writeln!(
+				svg,
+				"<rect x=\"{box_left:.1}\" y=\"{:.1}\" width=\"{box_width:.1}\" height=\"{:.1}\" \
+				 fill=\"white\" fill-opacity=\"0.8\" stroke=\"#c0c0c0\"/>",
+				top + 8.0,
+				8.0 + 16.0 * legend.len() as f64
+			)
+			.unwrap();
+			for (i, (label, color)) in legend.iter().enumerate() {
+				let y = top + 20.0 + 16.0 * i as f64;
+				writeln!(
+					svg,
+					"<line x1=\"{:.1}\" y1=\"{y:.1}\" x2=\"{:.1}\" y2=\"{y:.1}\" stroke=\"{color}\" \
+					 stroke-width=\"2\"/>",
+					box_left + 6.0,
+					box_left + 26.0
+				)
+				.unwrap();
+				writeln!(
+					svg,
+					"<text x=\"{:.1}\" y=\"{:.1}\">{}</text>",
+					box_left + 30.0,
+					y + 4.0,
+					escape(label)
+				)
+				.unwrap();
+			}
+		}
+
+		svg.push_str("</svg>\n");
+		svg
+	}
+
+	pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+		fs::write(path, self.to_svg())
+	}
+
+	fn next_color(&self) -> usize {
+		match self.series.last() {
+			Some(series) => series.color + 1,
+			None => 0,
+		}
+	}
+
+	/// Returns the label given for an axis, or else the distinct labels of the series.
+	fn axis_label(&self, label: Option<&str>, series_label: fn(&Series) -> &String) -> String {
+		if let Some(label) = label {
+			return label.to_string();
+		}
+		let mut labels: Vec<&str> = Vec::new();
+		for series in &self.series {
+			let label = series_label(series).as_str();
+			if !label.is_empty() && !labels.contains(&label) {
+				labels.push(label);
+			}
+		}
+		labels.join(", ")
+	}
+
+	/// Returns the ranges of the finite values of every series.
+	fn ranges(&self) -> ((f64, f64), (f64, f64)) {
+		let points = self
+			.series
+			.iter()
+			.flat_map(|s| &s.points)
+			.filter(|(x, y)| x.is_finite() && y.is_finite());
+		let mut x_range = (f64::INFINITY, f64::NEG_INFINITY);
+		let mut y_range = x_range;
+		for &(x, y) in points {
+			x_range = (x_range.0.min(x), x_range.1.max(x));
+			y_range = (y_range.0.min(y), y_range.1.max(y));
+		}
+		(x_range, y_range)
+	}
+}
+
+fn column<'a>(run: &'a RunData, name: &str) -> impl Iterator<Item = f64> + 'a {
+	let Some(column) = run.column(name) else {
+		panic!("the run has no numeric channel `{name}`");
+	};
+	column.iter().copied()
+}
+
+/// Evenly spaced axis ticks at round numbers.
+struct Ticks {
+	start: f64,
+	end: f64,
+	step: f64,
+}
+
+impl Ticks {
+	fn values(&self) -> impl Iterator<Item = f64> + '_ {
+		let count = ((self.end - self.start) / self.step).round() as usize;
+		(0..=count).map(|i| self.start + i as f64 * self.step)
+	}
+
+	/// Formats a tick with as many decimals as the step needs.
+	fn format(&self, x: f64) -> String {
+		let decimals = (-self.step.log10().floor()).max(0.0) as usize;
+		// avoids printing -0
+		let x = if x.abs() < self.step * 1e-9 { 0.0 } else { x };
+		format!("{x:.decimals$}")
+	}
+}
+
+/// Chooses about five ticks that cover `range`, with a step of 1, 2 or 5 times a power of ten.
+fn ticks((min, max): (f64, f64)) -> Ticks {
+	let (min, max) = match (min.is_finite() && max.is_finite(), max - min) {
+		(false, _) => (0.0, 1.0),
+		(true, 0.0) => {
+			let pad = if min == 0.0 { 1.0 } else { min.abs() * 0.1 };
+			(min - pad, max + pad)
+		},
+		_ => (min, max),
+	};
+
+	let rough = (max - min) / 5.0;
+	let magnitude = 10f64.powf(rough.log10().floor());
+	let step = [1.0, 2.0, 5.0, 10.0]
+		.into_iter()
+		.map(|m| m * magnitude)
+		.find(|&step| step >= rough)
+		.unwrap();
+	Ticks {
+		start: (min / step).floor() * step,
+		end: (max / step).ceil() * step,
+		step,
+	}
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{ChannelType, Severity, recorder::Value};
+
+	#[test]
+	fn ticks_are_round_numbers() {
+		let t = ticks((0.3, 9.2));
+		assert_eq!((t.start, t.end, t.step), (0.0, 10.0, 2.0));
+		let t = ticks((-0.013, 0.011));
+		assert_eq!(t.step, 0.005);
+		assert_eq!(t.format(t.start), "-0.015");
+		let flat = ticks((5.0, 5.0));
+		assert!(flat.start < 5.0 && flat.end > 5.0);
+	}
+
+	#[test]
+	fn renders_series_events_and_overlays() {
+		let runs: Vec<RunData> = (1..=3)
+			.map(|k| {
+				let mut run = RunData::with_types(
+					vec!["altitude".into(), "range".into()],
+					vec![ChannelType::F64; 2],
+					vec!["m".into(), "m".into()],
+					vec![String::new(); 2],
+				);
+				for i in 0..=10 {
+					let t = i as f64;
+					let altitude = if i == 5 { f64::NAN } else { k as f64 * t };
+					run.push_values(t, &[Value::F64(altitude), Value::F64(2.0 * t)]);
+				}
+				run
+			})
+			.collect();
+		let apogee = Event {
+			t: 4.0,
+			name: "Apogee <1>".into(),
+			severity: Severity::Info,
+			fields: Vec::new(),
+		};
+
+		let svg = Plot::new("Flight & landing")
+			.time_history(&runs[0], "range")
+			.overlay(&runs, "altitude")
+			.events(&[apogee])
+			.to_svg();
+		assert!(svg.contains(">Flight &amp; landing</text>"));
+		assert!(svg.contains(">t [s]</text>"));
+		assert!(svg.contains(">range [m], altitude [m]</text>"));
+		assert!(svg.contains(">altitude (3 runs)</text>"));
+		assert!(svg.contains(">Apogee &lt;1&gt;</text>"));
+		// the NaN breaks each overlaid run in two
+		assert_eq!(svg.matches("<polyline").count(), 1 + 3 * 2);
+
+		let trajectory = Plot::new("Trajectory").xy(&runs[1], "range", "altitude");
+		assert!(trajectory.to_svg().contains(">range [m]</text>"));
+	}
+
+	#[test]
+	fn plots_a_recorder_without_a_file() {
+		let mut exec = crate::Executor::<f64>::new(0.5, 2.0);
+		exec.set_integrator(|x, _| vec![*x], |_, _, _| vec![1.0], |x, y| *x = y[0]);
+		let mut recorder = crate::Recorder::in_memory();
+		recorder.track("x", |x| *x).unit("m");
+		recorder.track("twice", |x| 2.0 * x);
+		exec.add_recorder(recorder);
+		let result = exec.run(0.0);
+
+		let svg = Plot::time_histories("Run", &result.recordings[0], &["x", "twice"]).to_svg();
+		assert!(svg.contains(">x [m], twice</text>"));
+		assert_eq!(svg.matches("<polyline").count(), 2);
+	}
+}
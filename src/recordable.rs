@@ -0,0 +1,277 @@
+//! Variables that can be found and recorded by name.
+
+use std::rc::Rc;
+
+use crate::recorder::{ChannelType, Value};
+
+/// A recordable variable somewhere inside a value of type `S`.
+pub struct Variable<S> {
+	name: String,
+	unit: String,
+	ty: ChannelType,
+	accessor: Rc<dyn Fn(&S) -> Value>,
+}
+
+impl<S: 'static> Variable<S> {
+	/// Creates a variable for the whole of `S`, with an empty name.
+	pub fn new<F>(ty: ChannelType, accessor: F) -> Self
+	where
+		F: Fn(&S) -> Value + 'static,
+	{
+		Self {
+			name: String::new(),
+			unit: String::new(),
+			ty,
+			accessor: Rc::new(accessor),
+		}
+	}
+
+	/// Moves the variable into a field of a containing value, prefixing its name with `field`.
+	pub fn nest<P, F>(self, field: &str, get: F) -> Variable<P>
+	where
+		P: 'static,
+		F: Fn(&P) -> &S + 'static,
+	{
+		let name = match self.name.as_str() {
+			"" => field.to_string(),
+			name if name.starts_with('[') => format!("{field}{name}"),
+			name => format!("{field}.{name}"),
+		};
+		let accessor = self.accessor;
+		Variable {
+			name,
+			unit: self.unit,
+			ty: self.ty,
+			accessor: Rc::new(move |parent| accessor(get(parent))),
+		}
+	}
+
+	pub fn with_unit(mut self, unit: &str) -> Self {
+		self.unit = unit.to_string();
+		self
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn unit(&self) -> &str {
+		&self.unit
+	}
+
+	pub fn channel_type(&self) -> &ChannelType {
+		&self.ty
+	}
+
+	pub fn get(&self, value: &S) -> Value {
+		(self.accessor)(value)
+	}
+}
+
+/// A type whose variables can be recorded by name.
+///
+/// This is usually derived with `#[derive(Recordable)]`, which names each variable after the path of
+/// fields leading to it.
+pub trait Recordable: Sized + 'static {
+	fn variables() -> Vec<Variable<Self>>;
+}
+
+macro_rules! impl_recordable {
+	($($ty:ty => $channel:ident($value:ident, $convert:ty)),+ $(,)?) => {$(
+		impl Recordable for $ty {
+			fn variables() -> Vec<Variable<Self>> {
+				vec![Variable::new(ChannelType::$channel, |x: &Self| {
+					Value::$value(<$convert>::from(*x))
+				})]
+			}
+		}
+	)+};
+}
+
+impl_recordable! {
+	f64 => F64(F64, f64),
+	f32 => F64(F64, f64),
+	bool => Bool(Bool, bool),
+	i8 => Int(Int, i64),
+	i16 => Int(Int, i64),
+	i32 => Int(Int, i64),
+	i64 => Int(Int, i64),
+	u8 => Uint(Uint, u64),
+	u16 => Uint(Uint, u64),
+	u32 => Uint(Uint, u64),
+	u64 => Uint(Uint, u64),
+}
+
+impl Recordable for usize {
+	fn variables() -> Vec<Variable<Self>> {
+		vec![Variable::new(ChannelType::Uint, |x: &Self| {
+			Value::Uint(*x as u64)
+		})]
+	}
+}
+
+impl Recordable for isize {
+	fn variables() -> Vec<Variable<Self>> {
+		vec![Variable::new(ChannelType::Int, |x: &Self| {
+			Value::Int(*x as i64)
+		})]
+	}
+}
+
+impl<T: Recordable, const N: usize> Recordable for [T; N] {
+	fn variables() -> Vec<Variable<Self>> {
+		(0..N)
+			.flat_map(|i| {
+				T::variables()
+					.into_iter()
+					.map(move |v| v.nest(&format!("[{i}]"), move |array: &Self| &array[i]))
+			})
+			.collect()
+	}
+}
+
+macro_rules! impl_recordable_tuple {
+	($($field:tt: $ty:ident),+) => {
+		impl<$($ty: Recordable),+> Recordable for ($($ty,)+) {
+			fn variables() -> Vec<Variable<Self>> {
+				let mut variables = Vec::new();
+				$(
+					variables.extend(
+						$ty::variables()
+							.into_iter()
+							.map(|v| v.nest(stringify!($field), |tuple: &Self| &tuple.$field)),
+					);
+				)+
+				variables
+			}
+		}
+	};
+}
+
+impl_recordable_tuple!(0: A);
+impl_recordable_tuple!(0: A, 1: B);
+impl_recordable_tuple!(0: A, 1: B, 2: C);
+impl_recordable_tuple!(0: A, 1: B, 2: C, 3: D);
+
+#[cfg(feature = "glam")]
+macro_rules! impl_recordable_glam {
+	($($ty:ty => [$($field:ident),+]),+ $(,)?) => {$(
+		impl Recordable for $ty {
+			fn variables() -> Vec<Variable<Self>> {
+				let mut variables = Vec::new();
+				$(
+					variables.extend(
+						Recordable::variables()
+							.into_iter()
+							.map(|v| v.nest(stringify!($field), |value: &Self| &value.$field)),
+					);
+				)+
+				variables
+			}
+		}
+	)+};
+}
+
+#[cfg(feature = "glam")]
+impl_recordable_glam! {
+	glam::DVec2 => [x, y],
+	glam::DVec3 => [x, y, z],
+	glam::DVec4 => [x, y, z, w],
+	glam::DQuat => [x, y, z, w],
+	glam::Vec2 => [x, y],
+	glam::Vec3 => [x, y, z],
+	glam::Vec4 => [x, y, z, w],
+	glam::Quat => [x, y, z, w],
+}
+
+/// Returns true if `name` matches a glob `pattern`, where `*` matches any part of one segment of
+/// a dotted name and `**` matches any number of segments.
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+	if let Some(rest) = pattern.strip_prefix("**") {
+		return (0..=name.len())
+			.filter(|&i| name.is_char_boundary(i))
+			.any(|i| glob_match(rest, &name[i..]));
+	}
+	if let Some(rest) = pattern.strip_prefix('*') {
+		let segment_end = name.find('.').unwrap_or(name.len());
+		return (0..=segment_end)
+			.filter(|&i| name.is_char_boundary(i))
+			.any(|i| glob_match(rest, &name[i..]));
+	}
+	match (pattern.chars().next(), name.chars().next()) {
+		(None, None) => true,
+		(Some(p), Some(n)) if p == n => glob_match(&pattern[p.len_utf8()..], &name[n.len_utf8()..]),
+		_ => false,
+	}
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+	use super::*;
+	use crate::Recordable;
+
+	#[derive(Recordable)]
+	#[allow(dead_code)]
+	enum Phase {
+		Boost,
+		Coast,
+	}
+
+	#[derive(Recordable)]
+	struct Body {
+		#[record(unit = "m")]
+		position: [f64; 2],
+		#[record(rename = "v")]
+		speed: f32,
+		#[record(skip)]
+		#[allow(dead_code)]
+		name: String,
+	}
+
+	#[derive(Recordable)]
+	struct Vehicle {
+		body: Body,
+		phase: Phase,
+		stage: (u8, bool),
+	}
+
+	#[test]
+	fn derived_names_are_hierarchical() {
+		let vehicle = Vehicle {
+			body: Body {
+				position: [1.0, 2.0],
+				speed: 3.0,
+				name: String::new(),
+			},
+			phase: Phase::Coast,
+			stage: (2, true),
+		};
+
+		let variables = Vehicle::variables();
+		let names: Vec<&str> = variables.iter().map(Variable::name).collect();
+		assert_eq!(
+			names,
+			[
+				"body.position[0]",
+				"body.position[1]",
+				"body.v",
+				"phase",
+				"stage.0",
+				"stage.1"
+			]
+		);
+		assert_eq!(variables[1].unit(), "m");
+		assert_eq!(variables[1].get(&vehicle), Value::F64(2.0));
+		assert_eq!(variables[3].get(&vehicle), Value::Enum(1));
+		assert_eq!(variables[4].get(&vehicle), Value::Uint(2));
+	}
+
+	#[test]
+	fn globs() {
+		assert!(glob_match("rocket.*.x", "rocket.velocity.x"));
+		assert!(!glob_match("rocket.*.x", "rocket.motor.thrust.x"));
+		assert!(glob_match("rocket.**.x", "rocket.motor.thrust.x"));
+		assert!(glob_match("*.pos*", "body.position[0]"));
+		assert!(!glob_match("body.v", "body.velocity"));
+	}
+}
@@ -1,59 +1,934 @@
+mod binary;
+mod components;
+mod csv;
+mod trick;
+mod value;
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
 use std::fs::File;
-use std::io::{self, Write};
+use std::io;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::integrator::{IntegratorStat, IntegratorStats};
+use crate::recordable::{self, Recordable, Variable};
+use crate::run_data::RunData;
+use crate::units::Quantity;
+
+pub use binary::{INDEX_STRIDE, RecordingReader};
+pub use components::Components;
+pub(crate) use csv::{ChannelMetadata, CsvWriter, read_metadata, write_metadata};
+pub use csv::{CsvOptions, Notation};
+pub use trick::{TRICK_TIME, TrickReader};
+pub use value::{ChannelType, Value};
+
+type Accessor<S> = Box<dyn Fn(&S) -> Value>;
+type Predicate<S> = Box<dyn Fn(&S) -> bool>;
+
+/// Where the values of a recorded variable come from.
+enum Source<S> {
+	State(Accessor<S>),
+	Integrator { group: String, stat: IntegratorStat },
+}
+
+/// Records a window of samples around each time a predicate becomes true.
+struct Trigger<S> {
+	predicate: Predicate<S>,
+	pre_duration: f64,
+	post_duration: f64,
+	/// Samples from the last `pre_duration` seconds, kept while waiting for the trigger.
+	history: VecDeque<(f64, Vec<Value>)>,
+	/// End of the current post-trigger window, if the trigger has fired.
+	recording_until: Option<f64>,
+	was_true: bool,
+}
+
+impl<S> Trigger<S> {
+	/// Takes a sample and returns the samples that should be recorded.
+	fn process(&mut self, state: &S, t: f64, row: Vec<Value>) -> Vec<(f64, Vec<Value>)> {
+		let is_true = (self.predicate)(state);
+		let fired = is_true && !self.was_true;
+		self.was_true = is_true;
+
+		let mut rows = Vec::new();
+		if fired && self.recording_until.is_none() {
+			self.recording_until = Some(t + self.post_duration);
+			self.discard_history_before(t - self.pre_duration);
+			rows.extend(self.history.drain(..));
+		}
+
+		match self.recording_until {
+			Some(until) => {
+				if t >= until {
+					self.recording_until = None;
+				}
+				rows.push((t, row));
+			},
+			None => {
+				self.history.push_back((t, row));
+				self.discard_history_before(t - self.pre_duration);
+			},
+		}
+
+		rows
+	}
+
+	fn reset(&mut self) {
+		self.history.clear();
+		self.recording_until = None;
+		self.was_true = false;
+	}
+
+	fn discard_history_before(&mut self, t: f64) {
+		while self.history.front().is_some_and(|(t_old, _)| *t_old < t) {
+			self.history.pop_front();
+		}
+	}
+}
+
+/// The file format written by a [`Recorder`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+	/// Comma-separated values with a header row of names, and units and descriptions in a sidecar
+	/// `.meta.csv` file. The delimiter and number formatting are set with
+	/// [`Recorder::set_csv_options`].
+	#[default]
+	Csv,
+	/// The compact binary format read by [`RecordingReader`], optionally with an index of row times
+	/// for seeking.
+	Binary { index: bool },
+	/// Trick's binary `.trk` format, which Trick's data products tools can open.
+	Trick,
+}
+
+/// Where in each frame a [`Recorder`] takes its sample.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SamplePoint {
+	/// Before the `PreIntegrate` jobs, stamped with the time at the start of the frame.
+	FrameStart,
+	/// After integration and before the `PostIntegrate` jobs, stamped with the time at the end of
+	/// the frame.
+	AfterIntegration,
+	/// After the `PostIntegrate` jobs, stamped with the time at the end of the frame.
+	#[default]
+	FrameEnd,
+}
+
+/// What a format needs to know about the recording before the first row.
+pub(crate) struct Header {
+	/// Names of the recorded channels, not including time.
+	pub names: Vec<String>,
+	pub types: Vec<ChannelType>,
+	pub units: Vec<String>,
+	pub descriptions: Vec<String>,
+	/// Time between samples, or `None` if every frame is recorded.
+	pub interval: Option<f64>,
+}
+
+/// Writes rows in one of the file formats.
+///
+/// Writers buffer their output, but must write each row to the buffer whole so that a flushed file
+/// always ends on a complete row.
+pub(crate) trait RowWriter {
+	fn write_row(&mut self, t: f64, row: &[Value]) -> io::Result<()>;
 
+	fn flush(&mut self) -> io::Result<()>;
+
+	/// Writes anything that comes after the rows and flushes.
+	fn finish(&mut self) -> io::Result<()>;
+}
+
+/// The metadata of channels that were just tracked, set by chaining calls like
+/// `recorder.track("altitude", |sim| sim.position.y).unit("m")`.
+pub struct Tracked<'a> {
+	units: &'a mut [String],
+	descriptions: &'a mut [String],
+}
+
+impl Tracked<'_> {
+	/// Sets the unit of the channels, such as `m/s`.
+	pub fn unit(self, unit: &str) -> Self {
+		self.units.fill(unit.to_string());
+		self
+	}
+
+	pub fn description(self, description: &str) -> Self {
+		self.descriptions.fill(description.to_string());
+		self
+	}
+}
+
+/// Records tracked values to a file.
+///
+/// Rows are streamed to the file through a buffer as the run goes, so memory use does not grow with
+/// the length of the run. Each row is written to the buffer whole, so the file always ends on a
+/// complete row, and the buffer is flushed when the recorder is dropped, including while unwinding
+/// from a panic.
 pub struct Recorder<S> {
 	names: Vec<String>,
-	accessors: Vec<Box<dyn Fn(&S) -> f64>>,
-	times: Vec<f64>,
-	data: Vec<Vec<f64>>,
-	file_path: PathBuf,
+	types: Vec<ChannelType>,
+	units: Vec<String>,
+	descriptions: Vec<String>,
+	sources: Vec<Source<S>>,
+	file_path: Option<PathBuf>,
+	format: Format,
+	csv_options: CsvOptions,
+	keep_in_memory: bool,
+	data: Option<RunData>,
+	writer: Option<Box<dyn RowWriter>>,
+	flush_interval: Option<f64>,
+	last_flush_time: f64,
+	// time of the last row written this run
+	last_row_time: Option<f64>,
+	interval: Option<f64>,
+	sample_point: SamplePoint,
+	initial_sample: bool,
+	final_sample: bool,
+	start_time: f64,
+	stop_time: f64,
+	next_sample_time: Option<f64>,
+	condition: Option<Predicate<S>>,
+	start_condition: Option<Predicate<S>>,
+	stop_condition: Option<Predicate<S>>,
+	started: bool,
+	stopped: bool,
+	trigger: Option<Trigger<S>>,
 }
 
 impl<S> Recorder<S> {
 	pub fn new<P: AsRef<Path>>(file_path: P) -> Self {
+		Self {
+			file_path: Some(file_path.as_ref().to_path_buf()),
+			keep_in_memory: false,
+			..Self::in_memory()
+		}
+	}
+
+	/// Creates a recorder that writes no file and keeps its samples in memory instead.
+	///
+	/// The samples are returned in [`RunResult::recordings`](crate::RunResult::recordings).
+	pub fn in_memory() -> Self {
 		Self {
 			names: Vec::new(),
-			accessors: Vec::new(),
-			times: Vec::new(),
-			data: Vec::new(),
-			file_path: file_path.as_ref().to_path_buf(),
+			types: Vec::new(),
+			units: Vec::new(),
+			descriptions: Vec::new(),
+			sources: Vec::new(),
+			file_path: None,
+			format: Format::Csv,
+			csv_options: CsvOptions::new(),
+			keep_in_memory: true,
+			data: None,
+			writer: None,
+			flush_interval: None,
+			last_flush_time: f64::NEG_INFINITY,
+			last_row_time: None,
+			interval: None,
+			sample_point: SamplePoint::FrameEnd,
+			initial_sample: true,
+			final_sample: false,
+			start_time: f64::NEG_INFINITY,
+			stop_time: f64::INFINITY,
+			next_sample_time: None,
+			condition: None,
+			start_condition: None,
+			stop_condition: None,
+			started: false,
+			stopped: false,
+			trigger: None,
+		}
+	}
+
+	/// Sets the file format, which is CSV by default.
+	pub fn set_format(&mut self, format: Format) {
+		self.format = format;
+	}
+
+	/// Sets how CSV recordings are written, such as the delimiter and the precision of numbers.
+	pub fn set_csv_options(&mut self, options: CsvOptions) {
+		self.csv_options = options;
+	}
+
+	/// Also keeps the samples in memory, to be returned in
+	/// [`RunResult::recordings`](crate::RunResult::recordings).
+	///
+	/// Memory use then grows with the length of the run.
+	pub fn set_keep_in_memory(&mut self, keep: bool) {
+		self.keep_in_memory = keep || self.file_path.is_none();
+	}
+
+	/// Sets how many samples are recorded per second of simulation time.
+	///
+	/// Samples are taken on the first frame at or after each sample time, so the rate is independent
+	/// of the integration `dt`. By default, every frame is recorded.
+	pub fn set_rate(&mut self, hz: f64) {
+		self.set_interval(1.0 / hz);
+	}
+
+	/// Sets the simulation time between samples.
+	pub fn set_interval(&mut self, interval: f64) {
+		assert!(interval > 0.0, "recording interval must be positive");
+		self.interval = Some(interval);
+	}
+
+	/// Sets where in each frame the sample is taken.
+	pub fn set_sample_point(&mut self, point: SamplePoint) {
+		self.sample_point = point;
+	}
+
+	/// Sets whether the initial state, after the `Init` jobs, is recorded at the start time.
+	///
+	/// This is on by default. Recorders that sample before `PreIntegrate` always record the initial
+	/// state, as the first frame's sample.
+	pub fn set_initial_sample(&mut self, enabled: bool) {
+		self.initial_sample = enabled;
+	}
+
+	/// Sets whether the final state, after the `Shutdown` jobs, is recorded at the end time.
+	///
+	/// The final sample is recorded even if it falls between the samples set by the rate, but not if
+	/// a row was already recorded at the end time. It is off by default.
+	pub fn set_final_sample(&mut self, enabled: bool) {
+		self.final_sample = enabled;
+	}
+
+	/// Flushes buffered rows to disk at least every `interval` seconds of simulation time.
+	///
+	/// By default, rows are only written when the buffer fills up and at the end of the run.
+	pub fn set_flush_interval(&mut self, interval: f64) {
+		assert!(interval >= 0.0, "flush interval must not be negative");
+		self.flush_interval = Some(interval);
+	}
+
+	/// Only records samples at or after time `t`.
+	pub fn set_start_time(&mut self, t: f64) {
+		self.start_time = t;
+	}
+
+	/// Only records samples at or before time `t`.
+	pub fn set_stop_time(&mut self, t: f64) {
+		self.stop_time = t;
+	}
+
+	/// Only records samples while `condition` is true.
+	pub fn set_condition<F>(&mut self, condition: F)
+	where
+		F: Fn(&S) -> bool + 'static,
+	{
+		self.condition = Some(Box::new(condition));
+	}
+
+	/// Starts recording the first time `condition` is true.
+	pub fn set_start_condition<F>(&mut self, condition: F)
+	where
+		F: Fn(&S) -> bool + 'static,
+	{
+		self.start_condition = Some(Box::new(condition));
+	}
+
+	/// Stops recording for the rest of the run the first time `condition` is true.
+	pub fn set_stop_condition<F>(&mut self, condition: F)
+	where
+		F: Fn(&S) -> bool + 'static,
+	{
+		self.stop_condition = Some(Box::new(condition));
+	}
+
+	/// Only records samples around the times `predicate` becomes true.
+	///
+	/// While waiting, the samples from the last `pre_duration` seconds are kept in a buffer. When the
+	/// predicate changes from false to true, the buffered samples are recorded, followed by every
+	/// sample for the next `post_duration` seconds. After that, the recorder waits for the predicate
+	/// to become true again.
+	pub fn set_trigger<F>(&mut self, predicate: F, pre_duration: f64, post_duration: f64)
+	where
+		F: Fn(&S) -> bool + 'static,
+	{
+		self.trigger = Some(Trigger {
+			predicate: Box::new(predicate),
+			pre_duration,
+			post_duration,
+			history: VecDeque::new(),
+			recording_until: None,
+			was_true: false,
+		});
+	}
+
+	/// Returns true if a sample should be recorded at time `t`, and schedules the next sample.
+	fn is_due(&mut self, t: f64) -> bool {
+		// tolerance for times that are only off by accumulated round-off
+		let tolerance = 1e-9 * self.interval.unwrap_or(1.0);
+
+		if t < self.start_time - tolerance || t > self.stop_time + tolerance {
+			return false;
 		}
+
+		let Some(interval) = self.interval else {
+			return true;
+		};
+
+		// sample times are aligned to the start time (or zero), and a frame that overshoots a sample
+		// time records it late rather than shifting the ones after it
+		let origin = if self.start_time.is_finite() {
+			self.start_time
+		} else {
+			0.0
+		};
+		let next_sample_time = *self.next_sample_time.get_or_insert_with(|| {
+			origin + ((t - origin - tolerance) / interval).ceil().max(0.0) * interval
+		});
+		if t < next_sample_time - tolerance {
+			return false;
+		}
+
+		let samples_so_far = ((t - origin + tolerance) / interval).floor();
+		self.next_sample_time = Some(origin + (samples_so_far + 1.0) * interval);
+
+		true
 	}
 
-	pub fn track<F>(&mut self, name: &str, accessor: F)
+	pub fn track<F>(&mut self, name: &str, accessor: F) -> Tracked<'_>
 	where
 		F: (Fn(&S) -> f64) + 'static,
 	{
-		self.names.push(name.to_string());
-		self.accessors.push(Box::new(accessor));
+		self.add_channel(name, ChannelType::F64, move |state| Value::F64(accessor(state)))
+	}
+
+	pub fn track_bool<F>(&mut self, name: &str, accessor: F) -> Tracked<'_>
+	where
+		F: (Fn(&S) -> bool) + 'static,
+	{
+		self.add_channel(name, ChannelType::Bool, move |state| Value::Bool(accessor(state)))
 	}
 
-	pub(crate) fn sample(&mut self, state: &S, t: f64) {
-		self.times.push(t);
-		let row: Vec<f64> = self.accessors.iter().map(|f| f(state)).collect();
-		self.data.push(row);
+	pub fn track_int<F>(&mut self, name: &str, accessor: F) -> Tracked<'_>
+	where
+		F: (Fn(&S) -> i64) + 'static,
+	{
+		self.add_channel(name, ChannelType::Int, move |state| Value::Int(accessor(state)))
 	}
 
-	pub(crate) fn write_csv(&self) -> io::Result<()> {
-		assert!(self.times.len() == self.data.len());
+	pub fn track_uint<F>(&mut self, name: &str, accessor: F) -> Tracked<'_>
+	where
+		F: (Fn(&S) -> u64) + 'static,
+	{
+		self.add_channel(name, ChannelType::Uint, move |state| Value::Uint(accessor(state)))
+	}
 
-		let mut file = File::create(&self.file_path)?;
+	/// Tracks a physical quantity, which is recorded in SI units with its unit set accordingly.
+	pub fn track_quantity<Q, F>(&mut self, name: &str, accessor: F) -> Tracked<'_>
+	where
+		Q: Quantity,
+		F: (Fn(&S) -> Q) + 'static,
+	{
+		self.track(name, move |state| accessor(state).si())
+			.unit(Q::UNIT)
+	}
 
-		// header
-		write!(file, "time,")?;
-		writeln!(file, "{}", self.names.join(","))?;
+	/// Tracks each component of an array or vector as its own channel.
+	///
+	/// The channels are named by appending a suffix for each component to `name`, so
+	/// `track_vec("pos", |sim| sim.pos)` records `pos[0]`, `pos[1]`, ... for an array, or `pos.x`,
+	/// `pos.y`, ... for a `glam` vector or quaternion (with the `glam` feature).
+	pub fn track_vec<T, F>(&mut self, name: &str, accessor: F) -> Tracked<'_>
+	where
+		T: Components,
+		F: (Fn(&S) -> T) + 'static,
+	{
+		let first = self.names.len();
+		let accessor = Rc::new(accessor);
+		for (i, suffix) in T::suffixes().iter().enumerate() {
+			let accessor = Rc::clone(&accessor);
+			self.track(&format!("{name}{suffix}"), move |state| accessor(state).components()[i]);
+		}
+		self.tracked_from(first)
+	}
 
-		// data rows
-		for (t, data) in self.times.iter().zip(&self.data) {
-			let data_str = data
+	/// Tracks an enum, which is recorded by name.
+	///
+	/// `variants` lists every value the enum can take, and their `Debug` output is used as the names.
+	///
+	/// # Panics
+	///
+	/// Panics during the run if the accessor returns a value that is not in `variants`.
+	pub fn track_enum<E, F>(&mut self, name: &str, variants: &[E], accessor: F) -> Tracked<'_>
+	where
+		E: Clone + PartialEq + Debug + 'static,
+		F: (Fn(&S) -> E) + 'static,
+	{
+		let names = variants.iter().map(|v| format!("{v:?}")).collect();
+		let variants = variants.to_vec();
+		let channel = name.to_string();
+		self.add_channel(name, ChannelType::Enum(names), move |state| {
+			let value = accessor(state);
+			let index = variants
 				.iter()
-				.map(|x| x.to_string())
-				.collect::<Vec<_>>()
-				.join(",");
-			writeln!(file, "{t},{data_str}")?;
+				.position(|v| *v == value)
+				.unwrap_or_else(|| {
+					panic!("value {value:?} of `{channel}` is not one of its listed variants")
+				});
+			Value::Enum(index as u32)
+		})
+	}
+
+	/// Tracks a string, which is shortened to at most `max_len` bytes.
+	pub fn track_string<F>(&mut self, name: &str, max_len: usize, accessor: F) -> Tracked<'_>
+	where
+		F: (Fn(&S) -> String) + 'static,
+	{
+		self.add_channel(name, ChannelType::Str(max_len), move |state| {
+			let mut s = accessor(state);
+			value::truncate(&mut s, max_len);
+			Value::Str(s)
+		})
+	}
+
+	fn add_channel<F>(&mut self, name: &str, ty: ChannelType, accessor: F) -> Tracked<'_>
+	where
+		F: (Fn(&S) -> Value) + 'static,
+	{
+		self.names.push(name.to_string());
+		self.types.push(ty);
+		self.units.push(String::new());
+		self.descriptions.push(String::new());
+		self.sources.push(Source::State(Box::new(accessor)));
+		self.tracked_from(self.names.len() - 1)
+	}
+
+	/// Tracks a statistic of the named integration group, such as its step size.
+	///
+	/// The value is NaN if the executor has no group with that name.
+	pub fn track_integrator(
+		&mut self,
+		name: &str,
+		group: &str,
+		stat: IntegratorStat,
+	) -> Tracked<'_> {
+		self.names.push(name.to_string());
+		self.types.push(ChannelType::F64);
+		self.units.push(String::new());
+		self.descriptions.push(String::new());
+		self.sources.push(Source::Integrator {
+			group: group.to_string(),
+			stat,
+		});
+		self.tracked_from(self.names.len() - 1)
+	}
+
+	/// Returns the metadata of the channels from index `first` on.
+	fn tracked_from(&mut self, first: usize) -> Tracked<'_> {
+		Tracked {
+			units: &mut self.units[first..],
+			descriptions: &mut self.descriptions[first..],
+		}
+	}
+
+	fn add_variable(&mut self, prefix: &str, variable: Variable<S>)
+	where
+		S: 'static,
+	{
+		let name = match prefix {
+			"" => variable.name().to_string(),
+			prefix => format!("{prefix}.{}", variable.name()),
+		};
+		let unit = variable.unit().to_string();
+		self.add_channel(&name, variable.channel_type().clone(), move |state| variable.get(state))
+			.unit(&unit);
+	}
+}
+
+impl<S: Recordable> Recorder<S> {
+	/// Tracks every variable of the simulation, named by its path of fields under `prefix`.
+	///
+	/// With an empty prefix the names are just the field paths, like `rocket.velocity.x`.
+	pub fn track_all(&mut self, prefix: &str) {
+		for variable in S::variables() {
+			self.add_variable(prefix, variable);
+		}
+	}
+
+	/// Tracks the variables whose path of fields matches a glob pattern, and returns how many were
+	/// tracked.
+	///
+	/// In the pattern, `*` matches within one field name and `**` matches any number of fields, so
+	/// `rocket.*.x` matches `rocket.velocity.x` but not `rocket.motor.thrust.x`. The pattern is
+	/// matched against the path before `prefix` is added.
+	pub fn track_matching(&mut self, prefix: &str, pattern: &str) -> usize {
+		let mut count = 0;
+		for variable in S::variables() {
+			if recordable::glob_match(pattern, variable.name()) {
+				self.add_variable(prefix, variable);
+				count += 1;
+			}
 		}
+		count
+	}
+}
 
+impl<S> Recorder<S> {
+	/// Creates the file and writes the header, discarding the output and samples of any previous
+	/// run.
+	pub(crate) fn start(&mut self) -> io::Result<()> {
+		let header = Header {
+			names: self.names.clone(),
+			types: self.types.clone(),
+			units: self.units.clone(),
+			descriptions: self.descriptions.clone(),
+			interval: self.interval,
+		};
+		self.data = self.keep_in_memory.then(|| {
+			RunData::with_types(
+				header.names.clone(),
+				header.types.clone(),
+				header.units.clone(),
+				header.descriptions.clone(),
+			)
+		});
+		self.last_flush_time = f64::NEG_INFINITY;
+		self.last_row_time = None;
+		self.next_sample_time = None;
+		self.started = false;
+		self.stopped = false;
+		if let Some(trigger) = &mut self.trigger {
+			trigger.reset();
+		}
+
+		let Some(file_path) = &self.file_path else {
+			return Ok(());
+		};
+		let file = File::create(file_path)?;
+		if self.format == Format::Csv {
+			csv::write_metadata(file_path, &header)?;
+		}
+		let mut writer: Box<dyn RowWriter> = match self.format {
+			Format::Csv => Box::new(csv::CsvWriter::new(file, &header, &self.csv_options)?),
+			Format::Binary { index } => Box::new(binary::BinaryWriter::new(file, &header, index)?),
+			Format::Trick => Box::new(trick::TrickWriter::new(file, &header)?),
+		};
+		// make sure an aborted run still leaves a file with a header
+		writer.flush()?;
+		self.writer = Some(writer);
 		Ok(())
 	}
+
+	/// Records a sample at time `t` if one is due.
+	pub(crate) fn sample<'a, I>(&mut self, state: &S, t: f64, stats: I) -> io::Result<()>
+	where
+		I: IntoIterator<Item = &'a IntegratorStats> + Clone,
+	{
+		if !self.is_due(t) {
+			return Ok(());
+		}
+		self.record(state, t, stats)
+	}
+
+	/// Records a sample at time `t` if this recorder samples at `point` and one is due.
+	pub(crate) fn sample_at<'a, I>(
+		&mut self,
+		point: SamplePoint,
+		state: &S,
+		t: f64,
+		stats: I,
+	) -> io::Result<()>
+	where
+		I: IntoIterator<Item = &'a IntegratorStats> + Clone,
+	{
+		if point == self.sample_point {
+			self.sample(state, t, stats)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Records the state after the `Init` jobs, if enabled.
+	pub(crate) fn sample_initial<'a, I>(&mut self, state: &S, t: f64, stats: I) -> io::Result<()>
+	where
+		I: IntoIterator<Item = &'a IntegratorStats> + Clone,
+	{
+		// sampling before PreIntegrate already records the initial state on the first frame
+		if self.initial_sample && self.sample_point != SamplePoint::FrameStart {
+			self.sample(state, t, stats)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Records the state after the `Shutdown` jobs, if enabled, whether or not a sample is due.
+	pub(crate) fn sample_final<'a, I>(&mut self, state: &S, t: f64, stats: I) -> io::Result<()>
+	where
+		I: IntoIterator<Item = &'a IntegratorStats> + Clone,
+	{
+		let tolerance = 1e-9 * self.interval.unwrap_or(1.0);
+		let in_window = t >= self.start_time - tolerance && t <= self.stop_time + tolerance;
+		// a row at the same time would give the recording two values at one instant
+		let recorded = self
+			.last_row_time
+			.is_some_and(|last| (t - last).abs() <= tolerance);
+		if self.final_sample && in_window && !recorded {
+			self.record(state, t, stats)
+		} else {
+			Ok(())
+		}
+	}
+
+	fn record<'a, I>(&mut self, state: &S, t: f64, stats: I) -> io::Result<()>
+	where
+		I: IntoIterator<Item = &'a IntegratorStats> + Clone,
+	{
+		if !self.is_active(state) {
+			return Ok(());
+		}
+
+		let row: Vec<Value> = self
+			.sources
+			.iter()
+			.map(|source| match source {
+				Source::State(f) => f(state),
+				Source::Integrator { group, stat } => Value::F64(
+					stats
+						.clone()
+						.into_iter()
+						.find(|stats| stats.group == *group)
+						.map_or(f64::NAN, |stats| stats.get(*stat)),
+				),
+			})
+			.collect();
+
+		match &mut self.trigger {
+			Some(trigger) => {
+				for (t, row) in trigger.process(state, t, row) {
+					self.push_row(t, &row)?;
+				}
+			},
+			None => self.push_row(t, &row)?,
+		}
+
+		if self
+			.flush_interval
+			.is_some_and(|interval| t - self.last_flush_time >= interval)
+		{
+			self.flush(t)?;
+		}
+
+		Ok(())
+	}
+
+	/// Updates the start and stop conditions, returning true if samples should be recorded.
+	fn is_active(&mut self, state: &S) -> bool {
+		if !self.started {
+			self.started = self
+				.start_condition
+				.as_ref()
+				.is_none_or(|start| start(state));
+		}
+		if self.started && !self.stopped {
+			self.stopped = self.stop_condition.as_ref().is_some_and(|stop| stop(state));
+		}
+
+		let condition = self
+			.condition
+			.as_ref()
+			.is_none_or(|condition| condition(state));
+		self.started && !self.stopped && condition
+	}
+
+	fn push_row(&mut self, t: f64, row: &[Value]) -> io::Result<()> {
+		self.last_row_time = Some(t);
+		if let Some(data) = &mut self.data {
+			data.push_values(t, row);
+		}
+		match &mut self.writer {
+			Some(writer) => writer.write_row(t, row),
+			None => Ok(()),
+		}
+	}
+
+	fn flush(&mut self, t: f64) -> io::Result<()> {
+		self.last_flush_time = t;
+		match &mut self.writer {
+			Some(writer) => writer.flush(),
+			None => Ok(()),
+		}
+	}
+
+	/// Takes the samples kept in memory during the last run.
+	pub(crate) fn take_data(&mut self) -> Option<RunData> {
+		self.data.take()
+	}
+
+	/// Flushes and closes the file.
+	pub(crate) fn finish(&mut self) -> io::Result<()> {
+		match self.writer.take() {
+			Some(mut writer) => writer.finish(),
+			None => Ok(()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn trigger_records_window_around_rising_edge() {
+		let mut trigger = Trigger {
+			predicate: Box::new(|x: &f64| *x >= 5.0),
+			pre_duration: 2.0,
+			post_duration: 1.0,
+			history: VecDeque::new(),
+			recording_until: None,
+			was_true: false,
+		};
+
+		let recorded: Vec<f64> = (0..10)
+			.map(f64::from)
+			.flat_map(|t| trigger.process(&t, t, vec![Value::F64(t)]))
+			.map(|(t, _)| t)
+			.collect();
+
+		assert_eq!(recorded, [3.0, 4.0, 5.0, 6.0]);
+	}
+
+	#[test]
+	fn rows_are_flushed_before_the_run_ends() {
+		let path = std::env::temp_dir().join("simlib-recorder-flush.csv");
+		let mut recorder = Recorder::<f64>::new(&path);
+		recorder.track("x", |x| *x);
+		recorder.set_flush_interval(1.0);
+		recorder.start().unwrap();
+
+		for t in [0.0, 0.5, 1.0] {
+			recorder.sample(&(2.0 * t), t, []).unwrap();
+		}
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "time,x\n0.0,0.0\n0.5,1.0\n1.0,2.0\n");
+
+		recorder.sample(&3.0, 1.5, []).unwrap();
+		drop(recorder);
+		assert!(
+			std::fs::read_to_string(&path)
+				.unwrap()
+				.ends_with("1.0,2.0\n1.5,3.0\n")
+		);
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn vector_channels_are_expanded() {
+		let mut recorder = Recorder::<[f64; 3]>::in_memory();
+		recorder.track_vec("pos", |pos| *pos);
+		#[cfg(feature = "glam")]
+		recorder.track_vec("vel", |pos| glam::DVec3::from_array(*pos) * 2.0);
+		recorder.start().unwrap();
+		recorder.sample(&[1.0, 2.0, 3.0], 0.0, []).unwrap();
+
+		let data = recorder.take_data().unwrap();
+		assert_eq!(data.names()[..3], ["pos[0]", "pos[1]", "pos[2]"]);
+		assert_eq!(data.value_at("pos[2]", 0.0), Some(3.0));
+		#[cfg(feature = "glam")]
+		assert_eq!(data.value_at("vel.y", 0.0), Some(4.0));
+	}
+
+	#[cfg(feature = "derive")]
+	#[test]
+	fn recordable_variables_are_selected_by_pattern() {
+		#[derive(crate::Recordable)]
+		struct Motor {
+			#[record(unit = "N")]
+			thrust: [f64; 2],
+		}
+
+		#[derive(crate::Recordable)]
+		struct Rocket {
+			velocity: [f64; 2],
+			motor: Motor,
+		}
+
+		let mut recorder = Recorder::<Rocket>::in_memory();
+		assert_eq!(recorder.track_matching("rocket", "*[1]"), 1);
+		assert_eq!(recorder.track_matching("rocket", "motor.**"), 2);
+		recorder.start().unwrap();
+		let rocket = Rocket {
+			velocity: [1.0, 2.0],
+			motor: Motor { thrust: [3.0, 4.0] },
+		};
+		recorder.sample(&rocket, 0.0, []).unwrap();
+
+		let data = recorder.take_data().unwrap();
+		assert_eq!(
+			data.names(),
+			[
+				"rocket.velocity[1]",
+				"rocket.motor.thrust[0]",
+				"rocket.motor.thrust[1]"
+			]
+		);
+		assert_eq!(data.units(), ["", "N", "N"]);
+		assert_eq!(data.value_at("rocket.motor.thrust[1]", 0.0), Some(4.0));
+	}
+
+	#[test]
+	fn sample_points_in_the_frame() {
+		let mut exec = crate::Executor::<f64>::new(1.0, 2.0);
+		exec.add_job(crate::Phase::PostIntegrate, |x, _| *x += 10.0);
+		exec.add_job(crate::Phase::Shutdown, |x, _| *x = -1.0);
+		for (point, final_sample) in [
+			(SamplePoint::FrameEnd, false),
+			(SamplePoint::FrameStart, true),
+			(SamplePoint::AfterIntegration, true),
+		] {
+			let mut recorder = Recorder::in_memory();
+			recorder.track("x", |x| *x);
+			recorder.set_sample_point(point);
+			recorder.set_final_sample(final_sample);
+			exec.add_recorder(recorder);
+		}
+
+		let result = exec.run(0.0);
+		let samples = |i: usize| {
+			let data = &result.recordings[i];
+			(data.time().to_vec(), data.column("x").unwrap().to_vec())
+		};
+		assert_eq!(samples(0), (vec![0.0, 1.0, 2.0], vec![0.0, 10.0, 20.0]));
+		// the final sample is only added when no row was recorded at the end time
+		assert_eq!(samples(1), (vec![0.0, 1.0, 2.0], vec![0.0, 10.0, -1.0]));
+		assert_eq!(samples(2), (vec![0.0, 1.0, 2.0], vec![0.0, 0.0, 10.0]));
+	}
+
+	#[test]
+	fn rate_limited_sampling_restarts_with_each_run() {
+		let mut exec = crate::Executor::<f64>::new(0.25, 1.0);
+		let mut recorder = Recorder::in_memory();
+		recorder.track("x", |x| *x);
+		recorder.set_interval(0.5);
+		exec.add_recorder(recorder);
+
+		for _ in 0..2 {
+			let result = exec.run(0.0);
+			assert_eq!(result.recordings[0].time(), [0.0, 0.5, 1.0]);
+		}
+	}
+
+	#[test]
+	fn conditions_and_triggers_restart_with_each_run() {
+		let mut exec = crate::Executor::<f64>::new(0.25, 1.0);
+		exec.add_job(crate::Phase::PostIntegrate, |x, _| *x += 0.25);
+
+		let mut window = Recorder::in_memory();
+		window.track("x", |x| *x);
+		window.set_start_condition(|x| *x >= 0.25);
+		window.set_stop_condition(|x| *x >= 0.75);
+		exec.add_recorder(window);
+
+		let mut triggered = Recorder::in_memory();
+		triggered.track("x", |x| *x);
+		triggered.set_trigger(|x| *x >= 0.5, 0.25, 0.25);
+		exec.add_recorder(triggered);
+
+		for _ in 0..2 {
+			let result = exec.run(0.0);
+			assert_eq!(result.recordings[0].time(), [0.25, 0.5]);
+			assert_eq!(result.recordings[1].time(), [0.25, 0.5, 0.75]);
+		}
+	}
 }
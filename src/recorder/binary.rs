@@ -0,0 +1,551 @@
+//! A compact binary recording format.
+//!
+//! All numbers are little-endian. A file consists of a header, the rows and an optional index.
+//!
+//! The header is:
+//!
+//! | field            | type                                      |
+//! |------------------|-------------------------------------------|
+//! | magic            | the 8 bytes `SIMREC02`                    |
+//! | channel count    | `u32`, including the time channel         |
+//! | sample interval  | `f64`, NaN if every frame was recorded    |
+//! | channels         | one channel description per channel       |
+//!
+//! and each channel description is:
+//!
+//! | field       | type                                          |
+//! |-------------|-----------------------------------------------|
+//! | name        | `u16` byte length followed by UTF-8           |
+//! | type        | `u8` type code, followed by its parameters    |
+//! | unit        | `u16` byte length followed by UTF-8           |
+//! | description | `u16` byte length followed by UTF-8           |
+//!
+//! The types, their parameters and how their values are stored in a row are:
+//!
+//! | code | type   | parameters                                 | value                         |
+//! |------|--------|--------------------------------------------|-------------------------------|
+//! | 0    | `f64`  |                                            | `f64`                         |
+//! | 1    | `bool` |                                            | `u8`, 0 or 1                  |
+//! | 2    | `i64`  |                                            | `i64`                         |
+//! | 3    | `u64`  |                                            | `u64`                         |
+//! | 4    | enum   | `u32` variant count, then each name as above | `u32` index of the variant  |
+//! | 5    | string | `u16` maximum byte length                  | UTF-8 padded with zero bytes  |
+//!
+//! The first channel is always `time`, an `f64`. Every row has the same size, so rows can be
+//! located without reading the rows before them.
+//!
+//! The index is written when the recording finishes normally. It holds the time and row number of
+//! every [`INDEX_STRIDE`]th row as an `f64` and a `u64`, followed by the number of entries as a
+//! `u64` and the 8 bytes `SIMINDEX`. A file without an index (for example, from an aborted run) is
+//! still valid, and any incomplete row at its end is ignored.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::value::{decode, encode};
+use super::{ChannelType, Header, RowWriter, Value};
+use crate::run_data::Column;
+
+const MAGIC: &[u8; 8] = b"SIMREC02";
+// a channel with an empty name, unit and description takes 2 + 1 + 2 + 2 bytes of the header
+const MIN_CHANNEL_SIZE: u64 = 7;
+const INDEX_MAGIC: &[u8; 8] = b"SIMINDEX";
+
+/// Number of rows between index entries.
+pub const INDEX_STRIDE: u64 = 1024;
+
+pub(crate) struct BinaryWriter {
+	writer: BufWriter<File>,
+	types: Vec<ChannelType>,
+	rows: u64,
+	index: Option<Vec<(f64, u64)>>,
+}
+
+impl BinaryWriter {
+	pub(crate) fn new(file: File, header: &Header, index: bool) -> io::Result<Self> {
+		let mut writer = BufWriter::new(file);
+		writer.write_all(MAGIC)?;
+		writer.write_all(&(header.names.len() as u32 + 1).to_le_bytes())?;
+		writer.write_all(&header.interval.unwrap_or(f64::NAN).to_le_bytes())?;
+
+		write_channel(&mut writer, "time", &ChannelType::F64, "s", "")?;
+		for (i, name) in header.names.iter().enumerate() {
+			let (ty, unit, description) =
+				(&header.types[i], &header.units[i], &header.descriptions[i]);
+			write_channel(&mut writer, name, ty, unit, description)?;
+		}
+
+		Ok(Self {
+			writer,
+			types: header.types.clone(),
+			rows: 0,
+			index: index.then(Vec::new),
+		})
+	}
+}
+
+impl RowWriter for BinaryWriter {
+	fn write_row(&mut self, t: f64, row: &[Value]) -> io::Result<()> {
+		if let Some(index) = &mut self.index
+			&& self.rows.is_multiple_of(INDEX_STRIDE)
+		{
+			index.push((t, self.rows));
+		}
+		self.rows += 1;
+
+		let mut bytes = t.to_le_bytes().to_vec();
+		for (x, ty) in row.iter().zip(&self.types) {
+			encode(x, ty, &mut bytes);
+		}
+		self.writer.write_all(&bytes)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.writer.flush()
+	}
+
+	fn finish(&mut self) -> io::Result<()> {
+		if let Some(index) = self.index.take() {
+			for (t, row) in &index {
+				self.writer.write_all(&t.to_le_bytes())?;
+				self.writer.write_all(&row.to_le_bytes())?;
+			}
+			self.writer.write_all(&(index.len() as u64).to_le_bytes())?;
+			self.writer.write_all(INDEX_MAGIC)?;
+		}
+		self.writer.flush()
+	}
+}
+
+fn write_channel(
+	writer: &mut impl Write,
+	name: &str,
+	ty: &ChannelType,
+	unit: &str,
+	description: &str,
+) -> io::Result<()> {
+	write_str(writer, name)?;
+	match ty {
+		ChannelType::F64 => writer.write_all(&[0])?,
+		ChannelType::Bool => writer.write_all(&[1])?,
+		ChannelType::Int => writer.write_all(&[2])?,
+		ChannelType::Uint => writer.write_all(&[3])?,
+		ChannelType::Enum(variants) => {
+			writer.write_all(&[4])?;
+			writer.write_all(&(variants.len() as u32).to_le_bytes())?;
+			for variant in variants {
+				write_str(writer, variant)?;
+			}
+		},
+		ChannelType::Str(max_len) => {
+			let max_len = u16::try_from(*max_len).map_err(|_| {
+				io::Error::new(io::ErrorKind::InvalidInput, "string channel is too long")
+			})?;
+			writer.write_all(&[5])?;
+			writer.write_all(&max_len.to_le_bytes())?;
+		},
+	}
+	write_str(writer, unit)?;
+	write_str(writer, description)
+}
+
+fn write_str(writer: &mut impl Write, s: &str) -> io::Result<()> {
+	let len = u16::try_from(s.len())
+		.map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "channel name is too long"))?;
+	writer.write_all(&len.to_le_bytes())?;
+	writer.write_all(s.as_bytes())
+}
+
+/// Reads recordings written in the binary format.
+pub struct RecordingReader {
+	file: BufReader<File>,
+	names: Vec<String>,
+	types: Vec<ChannelType>,
+	units: Vec<String>,
+	descriptions: Vec<String>,
+	interval: Option<f64>,
+	row_size: u64,
+	data_start: u64,
+	rows: u64,
+	index: Vec<(f64, u64)>,
+}
+
+impl RecordingReader {
+	pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		let mut file = BufReader::new(File::open(path)?);
+
+		if read_array::<8>(&mut file)? != *MAGIC {
+			return Err(invalid_data("not a SIMREC02 simlib binary recording"));
+		}
+		let channels = u32::from_le_bytes(read_array(&mut file)?) as usize;
+		let interval = f64::from_le_bytes(read_array(&mut file)?);
+		// the count comes from the file, so check it could hold that many channels before
+		// allocating for them
+		if channels as u64 * MIN_CHANNEL_SIZE > file.get_ref().metadata()?.len() {
+			return Err(invalid_data("channel count is larger than the file"));
+		}
+
+		let mut names = Vec::with_capacity(channels);
+		let mut types = Vec::with_capacity(channels);
+		let mut units = Vec::with_capacity(channels);
+		let mut descriptions = Vec::with_capacity(channels);
+		for _ in 0..channels {
+			names.push(read_str(&mut file, "channel name")?);
+			types.push(read_type(&mut file)?);
+			units.push(read_str(&mut file, "unit")?);
+			descriptions.push(read_str(&mut file, "description")?);
+		}
+		if types.first() != Some(&ChannelType::F64) {
+			return Err(invalid_data("the first channel must be an f64 time"));
+		}
+
+		let data_start = file.stream_position()?;
+		let len = file.seek(SeekFrom::End(0))?;
+		let row_size: u64 = types.iter().map(|ty| ty.size() as u64).sum();
+
+		// the index is only present if the recording finished normally
+		let mut index = Vec::new();
+		let mut data_end = len;
+		if len >= data_start + 16 {
+			file.seek(SeekFrom::End(-16))?;
+			let entries = u64::from_le_bytes(read_array(&mut file)?);
+			let index_start = entries
+				.checked_mul(16)
+				.and_then(|size| len.checked_sub(16 + size));
+			if read_array::<8>(&mut file)? == *INDEX_MAGIC
+				&& let Some(index_start) = index_start
+				&& index_start >= data_start
+				&& (index_start - data_start).is_multiple_of(row_size)
+			{
+				file.seek(SeekFrom::Start(index_start))?;
+				for _ in 0..entries {
+					let t = f64::from_le_bytes(read_array(&mut file)?);
+					let row = u64::from_le_bytes(read_array(&mut file)?);
+					index.push((t, row));
+				}
+				data_end = index_start;
+			}
+		}
+
+		Ok(Self {
+			file,
+			names,
+			types,
+			units,
+			descriptions,
+			interval: (!interval.is_nan()).then_some(interval),
+			row_size,
+			data_start,
+			rows: (data_end - data_start) / row_size,
+			index,
+		})
+	}
+
+	/// Returns the channel names, starting with `time`.
+	pub fn names(&self) -> &[String] {
+		&self.names
+	}
+
+	/// Returns the type of each channel, starting with `time`.
+	pub fn types(&self) -> &[ChannelType] {
+		&self.types
+	}
+
+	/// Returns the unit of each channel, which is empty if it was not given.
+	pub fn units(&self) -> &[String] {
+		&self.units
+	}
+
+	/// Returns the description of each channel, which is empty if it was not given.
+	pub fn descriptions(&self) -> &[String] {
+		&self.descriptions
+	}
+
+	/// Returns the time between samples, or `None` if every frame was recorded.
+	pub fn sample_interval(&self) -> Option<f64> {
+		self.interval
+	}
+
+	/// Returns the number of complete rows.
+	pub fn len(&self) -> u64 {
+		self.rows
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.rows == 0
+	}
+
+	/// Returns true if the recording finished normally and has an index.
+	pub fn has_index(&self) -> bool {
+		!self.index.is_empty()
+	}
+
+	/// Reads one row, starting with its time.
+	///
+	/// Returns an error of kind [`io::ErrorKind::InvalidInput`] if there is no such row.
+	pub fn read_row(&mut self, row: u64) -> io::Result<Vec<Value>> {
+		let bytes = self.read_row_bytes(row)?;
+		Ok(self.decode_row(&bytes).collect())
+	}
+
+	fn read_time(&mut self, row: u64) -> io::Result<f64> {
+		let bytes = self.read_row_bytes(row)?;
+		Ok(f64::from_le_bytes(bytes[..8].try_into().unwrap()))
+	}
+
+	fn read_row_bytes(&mut self, row: u64) -> io::Result<Vec<u8>> {
+		if row >= self.rows {
+			return Err(row_out_of_range(row, self.rows));
+		}
+		self.file
+			.seek(SeekFrom::Start(self.data_start + row * self.row_size))?;
+		let mut bytes = vec![0; self.row_size as usize];
+		self.file.read_exact(&mut bytes)?;
+		Ok(bytes)
+	}
+
+	fn decode_row<'a>(&'a self, bytes: &'a [u8]) -> impl Iterator<Item = Value> + 'a {
+		let mut offset = 0;
+		self.types.iter().map(move |ty| {
+			let value = decode(&bytes[offset..offset + ty.size()], ty);
+			offset += ty.size();
+			value
+		})
+	}
+
+	/// Returns the first row recorded at or after time `t`, or the number of rows if there is none.
+	pub fn find_time(&mut self, t: f64) -> io::Result<u64> {
+		// narrow the search to one stride with the index, if there is one
+		let (mut low, mut high) = match self.index.partition_point(|(time, _)| *time < t) {
+			_ if self.index.is_empty() => (0, self.rows),
+			0 => (0, 0),
+			i => {
+				let high = self.index.get(i).map_or(self.rows, |(_, row)| *row);
+				(self.index[i - 1].1, high)
+			},
+		};
+
+		while low < high {
+			let mid = low + (high - low) / 2;
+			if self.read_time(mid)? < t {
+				low = mid + 1;
+			} else {
+				high = mid;
+			}
+		}
+		Ok(low)
+	}
+
+	/// Reads every row into one column per channel, starting with time.
+	pub fn read_columns(&mut self) -> io::Result<Vec<Column>> {
+		self.read_column_range(0, self.rows)
+	}
+
+	/// Reads the rows recorded between `start` and `end` (inclusive) into one column per channel.
+	pub fn read_columns_between(&mut self, start: f64, end: f64) -> io::Result<Vec<Column>> {
+		let first = self.find_time(start)?;
+		let mut last = first;
+		while last < self.rows && self.read_time(last)? <= end {
+			last += 1;
+		}
+		self.read_column_range(first, last)
+	}
+
+	fn read_column_range(&mut self, first: u64, last: u64) -> io::Result<Vec<Column>> {
+		let mut columns: Vec<Column> = self.types.iter().map(Column::new).collect();
+		if first == last {
+			return Ok(columns);
+		}
+
+		self.file
+			.seek(SeekFrom::Start(self.data_start + first * self.row_size))?;
+		let mut bytes = vec![0; self.row_size as usize];
+		for _ in first..last {
+			self.file.read_exact(&mut bytes)?;
+			for (column, x) in columns.iter_mut().zip(self.decode_row(&bytes)) {
+				column.push(x);
+			}
+		}
+		Ok(columns)
+	}
+}
+
+fn read_type(reader: &mut impl Read) -> io::Result<ChannelType> {
+	let [code] = read_array(reader)?;
+	Ok(match code {
+		0 => ChannelType::F64,
+		1 => ChannelType::Bool,
+		2 => ChannelType::Int,
+		3 => ChannelType::Uint,
+		4 => {
+			let count = u32::from_le_bytes(read_array(reader)?);
+			let variants = (0..count)
+				.map(|_| read_str(reader, "enum variant name"))
+				.collect::<io::Result<_>>()?;
+			ChannelType::Enum(variants)
+		},
+		5 => ChannelType::Str(u16::from_le_bytes(read_array(reader)?) as usize),
+		code => return Err(invalid_data(&format!("unknown channel type {code}"))),
+	})
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+	let mut bytes = [0; N];
+	reader.read_exact(&mut bytes)?;
+	Ok(bytes)
+}
+
+/// Reads a string, naming what it holds in the error if it is not UTF-8.
+fn read_str(reader: &mut impl Read, what: &str) -> io::Result<String> {
+	let len = u16::from_le_bytes(read_array(reader)?);
+	let mut bytes = vec![0; len as usize];
+	reader.read_exact(&mut bytes)?;
+	String::from_utf8(bytes).map_err(|_| invalid_data(&format!("{what} is not UTF-8")))
+}
+
+fn row_out_of_range(row: u64, rows: u64) -> io::Error {
+	io::Error::new(
+		io::ErrorKind::InvalidInput,
+		format!("row {row} is out of range for a recording of {rows} rows"),
+	)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::RunData;
+
+	fn write_recording(path: &Path, rows: u64, index: bool) {
+		let header = Header {
+			names: vec!["x".to_string()],
+			types: vec![ChannelType::F64],
+			units: vec!["m".to_string()],
+			descriptions: vec!["distance travelled".to_string()],
+			interval: Some(0.5),
+		};
+		let mut writer = BinaryWriter::new(File::create(path).unwrap(), &header, index).unwrap();
+		for i in 0..rows {
+			let t = 0.5 * i as f64;
+			writer.write_row(t, &[Value::F64(2.0 * t)]).unwrap();
+		}
+		if index {
+			writer.finish().unwrap();
+		}
+	}
+
+	#[test]
+	fn indexed_recording_round_trips_and_seeks() {
+		let path = std::env::temp_dir().join("simlib-binary-indexed.bin");
+		write_recording(&path, 3000, true);
+
+		let mut reader = RecordingReader::open(&path).unwrap();
+		assert_eq!(reader.names(), ["time", "x"]);
+		assert_eq!(reader.units(), ["s", "m"]);
+		assert_eq!(reader.descriptions(), ["", "distance travelled"]);
+		assert_eq!(reader.sample_interval(), Some(0.5));
+		assert_eq!(reader.len(), 3000);
+		assert!(reader.has_index());
+
+		assert_eq!(reader.find_time(1100.0).unwrap(), 2200);
+		assert_eq!(reader.find_time(1100.2).unwrap(), 2201);
+		assert_eq!(reader.find_time(-1.0).unwrap(), 0);
+		assert_eq!(reader.find_time(1e6).unwrap(), 3000);
+
+		let columns = reader.read_columns_between(10.0, 11.0).unwrap();
+		assert_eq!(
+			columns,
+			[
+				Column::Numeric(vec![10.0, 10.5, 11.0]),
+				Column::Numeric(vec![20.0, 21.0, 22.0])
+			]
+		);
+		assert_eq!(reader.read_columns().unwrap()[1].as_numeric().unwrap()[2999], 2999.0);
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn unfinished_recording_is_readable() {
+		let path = std::env::temp_dir().join("simlib-binary-unfinished.bin");
+		write_recording(&path, 10, false);
+		// simulate a row cut off part way through
+		let mut file = File::options().append(true).open(&path).unwrap();
+		file.write_all(&[0; 5]).unwrap();
+
+		let mut reader = RecordingReader::open(&path).unwrap();
+		assert_eq!(reader.len(), 10);
+		assert!(!reader.has_index());
+		assert_eq!(reader.find_time(2.0).unwrap(), 4);
+		assert_eq!(reader.read_row(9).unwrap(), [Value::F64(4.5), Value::F64(9.0)]);
+		assert_eq!(reader.read_row(10).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn typed_channels_round_trip() {
+		let path = std::env::temp_dir().join("simlib-binary-typed.bin");
+		let phases = vec!["Boost".to_string(), "Coast".to_string()];
+		let header = Header {
+			names: ["armed", "count", "steps", "phase", "motor"]
+				.map(String::from)
+				.to_vec(),
+			types: vec![
+				ChannelType::Bool,
+				ChannelType::Int,
+				ChannelType::Uint,
+				ChannelType::Enum(phases.clone()),
+				ChannelType::Str(4),
+			],
+			units: vec![String::new(); 5],
+			descriptions: vec![String::new(); 5],
+			interval: None,
+		};
+		let row = [
+			Value::Bool(true),
+			Value::Int(-7),
+			Value::Uint(u64::MAX),
+			Value::Enum(1),
+			Value::Str("I28".to_string()),
+		];
+		let mut writer = BinaryWriter::new(File::create(&path).unwrap(), &header, false).unwrap();
+		writer.write_row(0.25, &row).unwrap();
+		writer.finish().unwrap();
+
+		let mut reader = RecordingReader::open(&path).unwrap();
+		assert_eq!(reader.types()[4], ChannelType::Enum(phases));
+		assert_eq!(reader.types()[5], ChannelType::Str(4));
+		assert_eq!(reader.read_row(0).unwrap()[1..], row);
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn corrupt_channel_count_is_rejected() {
+		let path = std::env::temp_dir().join("simlib-binary-corrupt.bin");
+		let mut bytes = MAGIC.to_vec();
+		bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+		bytes.extend_from_slice(&f64::NAN.to_le_bytes());
+		std::fs::write(&path, bytes).unwrap();
+
+		let error = RecordingReader::open(&path).err().unwrap();
+		assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn other_versions_are_rejected() {
+		let path = std::env::temp_dir().join("simlib-binary-v1.bin");
+		write_recording(&path, 1, true);
+		let mut bytes = std::fs::read(&path).unwrap();
+		bytes[..8].copy_from_slice(b"SIMREC01");
+		std::fs::write(&path, bytes).unwrap();
+
+		let error = RunData::load(&path).err().unwrap();
+		assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+		assert!(error.to_string().contains("SIMREC02"), "{error}");
+		std::fs::remove_file(path).unwrap();
+	}
+}
@@ -0,0 +1,47 @@
+//! Values with several components, recorded as one channel per component.
+
+/// A value with a fixed number of components, such as an array or a vector.
+pub trait Components {
+	/// Returns the suffix appended to the channel name for each component, such as `[0]` or `.x`.
+	fn suffixes() -> Vec<String>;
+
+	/// Returns the components, in the same order as the suffixes.
+	fn components(&self) -> Vec<f64>;
+}
+
+impl<const N: usize> Components for [f64; N] {
+	fn suffixes() -> Vec<String> {
+		(0..N).map(|i| format!("[{i}]")).collect()
+	}
+
+	fn components(&self) -> Vec<f64> {
+		self.to_vec()
+	}
+}
+
+#[cfg(feature = "glam")]
+macro_rules! impl_glam_components {
+	($($ty:ty => [$($field:ident),+]),+ $(,)?) => {$(
+		impl Components for $ty {
+			fn suffixes() -> Vec<String> {
+				vec![$(concat!(".", stringify!($field)).to_string()),+]
+			}
+
+			fn components(&self) -> Vec<f64> {
+				vec![$(f64::from(self.$field)),+]
+			}
+		}
+	)+};
+}
+
+#[cfg(feature = "glam")]
+impl_glam_components! {
+	glam::DVec2 => [x, y],
+	glam::DVec3 => [x, y, z],
+	glam::DVec4 => [x, y, z, w],
+	glam::DQuat => [x, y, z, w],
+	glam::Vec2 => [x, y],
+	glam::Vec3 => [x, y, z],
+	glam::Vec4 => [x, y, z, w],
+	glam::Quat => [x, y, z, w],
+}
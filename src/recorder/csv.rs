@@ -0,0 +1,421 @@
+//! Comma-separated values with a header row of channel names.
+//!
+//! Booleans are written as `true` or `false` and enums by name. Line breaks in names and strings are
+//! replaced with spaces, and they are quoted if they contain the delimiter or a quote. How numbers
+//! are written, and the delimiter, are set with [`CsvOptions`].
+//!
+//! Types, units and descriptions are written to a sidecar file next to the recording, named by
+//! replacing its extension with `meta.csv`. The sidecar is itself CSV, with the columns `name`,
+//! `type`, `unit` and `description` and a row for each channel, starting with time. Types are
+//! written as `f64`, `bool`, `int`, `uint`, `str(N)` for strings of at most `N` bytes, and
+//! `enum(A|B|C)` with the variant names in order, which therefore cannot contain `|`. The sidecar
+//! is only written if some channel is not `f64` or has a unit or description.
+
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::{ChannelType, Header, RowWriter, Value};
+
+/// How `f64` values are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Notation {
+	/// The shortest form that reads back as exactly the same number, such as `0.1`, `2.0` or
+	/// `1e300`.
+	///
+	/// This loses nothing, so recordings can be compared bit for bit after reading them back.
+	#[default]
+	Shortest,
+	/// Fixed-point with the given number of digits after the decimal point, such as `0.100`.
+	Fixed(usize),
+	/// Scientific with the given number of digits after the decimal point, such as `1.00e-1`.
+	Scientific(usize),
+}
+
+/// Options for writing and reading CSV recordings.
+///
+/// The default is comma-separated, with numbers in [`Notation::Shortest`] and non-finite numbers as
+/// `NaN`, `inf` and `-inf`, which reads back exactly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsvOptions {
+	delimiter: char,
+	notation: Notation,
+	nan: String,
+	infinity: String,
+}
+
+impl Default for CsvOptions {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl CsvOptions {
+	pub fn new() -> Self {
+		Self {
+			delimiter: ',',
+			notation: Notation::Shortest,
+			nan: "NaN".to_string(),
+			infinity: "inf".to_string(),
+		}
+	}
+
+	/// Creates options for tab-separated values.
+	pub fn tsv() -> Self {
+		Self::new().delimiter('\t')
+	}
+
+	/// Sets the character between fields, which cannot be a quote or line break.
+	pub fn delimiter(mut self, delimiter: char) -> Self {
+		assert!(!matches!(delimiter, '"' | '\n' | '\r'), "invalid CSV delimiter {delimiter:?}");
+		self.delimiter = delimiter;
+		self
+	}
+
+	pub fn notation(mut self, notation: Notation) -> Self {
+		self.notation = notation;
+		self
+	}
+
+	/// Sets how NaN is written, such as an empty string.
+	pub fn nan(mut self, nan: &str) -> Self {
+		self.nan = nan.to_string();
+		self
+	}
+
+	/// Sets how infinity is written, with a `-` before it for negative infinity.
+	pub fn infinity(mut self, infinity: &str) -> Self {
+		self.infinity = infinity.to_string();
+		self
+	}
+
+	pub(crate) fn format_number(&self, x: f64, out: &mut String) {
+		match self.notation {
+			_ if x.is_nan() => out.push_str(&self.nan),
+			_ if x.is_infinite() => {
+				if x < 0.0 {
+					out.push('-');
+				}
+				out.push_str(&self.infinity);
+			},
+			// unlike `Display`, `Debug` switches to an exponent for very large and small numbers
+			Notation::Shortest => write!(out, "{x:?}").unwrap(),
+			Notation::Fixed(digits) => write!(out, "{x:.digits$}").unwrap(),
+			Notation::Scientific(digits) => write!(out, "{x:.digits$e}").unwrap(),
+		}
+	}
+
+	/// Reads a number written with these options, or in Rust's own format.
+	pub(crate) fn parse_number(&self, s: &str) -> Option<f64> {
+		let s = s.trim();
+		if s == self.nan {
+			return Some(f64::NAN);
+		}
+		if s.strip_prefix('-').unwrap_or(s) == self.infinity {
+			Some(if s.starts_with('-') {
+				f64::NEG_INFINITY
+			} else {
+				f64::INFINITY
+			})
+		} else {
+			s.parse().ok()
+		}
+	}
+
+	/// Makes a string safe to write as one field.
+	fn quote(&self, s: &str) -> String {
+		let s = s.replace(['\n', '\r'], " ");
+		if s.contains([self.delimiter, '"']) {
+			format!("\"{}\"", s.replace('"', "\"\""))
+		} else {
+			s
+		}
+	}
+
+	/// Splits a line into fields, removing the quotes around quoted fields.
+	pub(crate) fn split_fields(&self, line: &str) -> Vec<String> {
+		let mut fields = Vec::new();
+		let mut field = String::new();
+		let mut chars = line.chars().peekable();
+		let mut quoted = false;
+		while let Some(c) = chars.next() {
+			match c {
+				'"' if quoted && chars.peek() == Some(&'"') => {
+					field.push('"');
+					chars.next();
+				},
+				'"' => quoted = !quoted,
+				c if c == self.delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+				c => field.push(c),
+			}
+		}
+		fields.push(field);
+		fields
+	}
+}
+
+pub(crate) struct CsvWriter {
+	writer: BufWriter<File>,
+	types: Vec<ChannelType>,
+	options: CsvOptions,
+}
+
+impl CsvWriter {
+	pub(crate) fn new(file: File, header: &Header, options: &CsvOptions) -> io::Result<Self> {
+		let mut writer = BufWriter::new(file);
+		let mut line = "time".to_string();
+		for name in &header.names {
+			line.push(options.delimiter);
+			line.push_str(&options.quote(name));
+		}
+		writeln!(writer, "{line}")?;
+		Ok(Self {
+			writer,
+			types: header.types.clone(),
+			options: options.clone(),
+		})
+	}
+}
+
+impl RowWriter for CsvWriter {
+	fn write_row(&mut self, t: f64, row: &[Value]) -> io::Result<()> {
+		let options = &self.options;
+		let mut line = String::new();
+		options.format_number(t, &mut line);
+		for (x, ty) in row.iter().zip(&self.types) {
+			line.push(options.delimiter);
+			match x {
+				Value::F64(x) => options.format_number(*x, &mut line),
+				Value::Str(s) => line.push_str(&options.quote(s)),
+				Value::Enum(_) => line.push_str(&options.quote(&x.display(ty).to_string())),
+				_ => write!(line, "{}", x.display(ty)).unwrap(),
+			}
+		}
+		line.push('\n');
+		self.writer.write_all(line.as_bytes())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.writer.flush()
+	}
+
+	fn finish(&mut self) -> io::Result<()> {
+		self.writer.flush()
+	}
+}
+
+/// Returns the path of the sidecar file holding the units and descriptions of a CSV recording.
+pub(crate) fn metadata_path(path: &Path) -> PathBuf {
+	path.with_extension("meta.csv")
+}
+
+/// Writes the sidecar file for a CSV recording, or removes an old one if there is no metadata.
+pub(crate) fn write_metadata(path: &Path, header: &Header) -> io::Result<()> {
+	let path = metadata_path(path);
+	let has_metadata = header.types.iter().any(|ty| *ty != ChannelType::F64)
+		|| header
+			.units
+			.iter()
+			.chain(&header.descriptions)
+			.any(|s| !s.is_empty());
+	if !has_metadata {
+		return match fs::remove_file(path) {
+			Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+			_ => Ok(()),
+		};
+	}
+
+	let options = CsvOptions::new();
+	let quote = |s: &str| options.quote(s);
+	let mut writer = BufWriter::new(File::create(path)?);
+	writeln!(writer, "name,type,unit,description")?;
+	writeln!(writer, "time,f64,s,")?;
+	for (i, name) in header.names.iter().enumerate() {
+		let ty = type_name(&header.types[i]);
+		let (unit, description) = (&header.units[i], &header.descriptions[i]);
+		writeln!(writer, "{},{},{},{}", quote(name), quote(&ty), quote(unit), quote(description))?;
+	}
+	writer.flush()
+}
+
+/// The metadata of one channel of a CSV recording, read from its sidecar file.
+pub(crate) struct ChannelMetadata {
+	pub(crate) name: String,
+	pub(crate) ty: ChannelType,
+	pub(crate) unit: String,
+	pub(crate) description: String,
+}
+
+/// Reads the metadata of a CSV recording's channels from its sidecar file, or returns `None` if it
+/// has none.
+pub(crate) fn read_metadata(path: &Path) -> io::Result<Option<Vec<ChannelMetadata>>> {
+	let file = match File::open(metadata_path(path)) {
+		Ok(file) => file,
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+		Err(e) => return Err(e),
+	};
+	let options = CsvOptions::new();
+	let mut metadata = Vec::new();
+	for line in BufReader::new(file).lines().skip(1) {
+		let line = line?;
+		if let [name, ty, unit, description] = &options.split_fields(&line)[..] {
+			let ty = parse_type(ty).ok_or_else(|| {
+				io::Error::new(
+					io::ErrorKind::InvalidData,
+					format!("channel {name} has unknown type {ty:?}"),
+				)
+			})?;
+			metadata.push(ChannelMetadata {
+				name: name.clone(),
+				ty,
+				unit: unit.clone(),
+				description: description.clone(),
+			});
+		}
+	}
+	Ok(Some(metadata))
+}
+
+/// Names a channel type in the sidecar file.
+fn type_name(ty: &ChannelType) -> String {
+	match ty {
+		ChannelType::F64 => "f64".to_string(),
+		ChannelType::Bool => "bool".to_string(),
+		ChannelType::Int => "int".to_string(),
+		ChannelType::Uint => "uint".to_string(),
+		ChannelType::Enum(variants) => format!("enum({})", variants.join("|")),
+		ChannelType::Str(max_len) => format!("str({max_len})"),
+	}
+}
+
+/// Reads a channel type named by [`type_name`].
+fn parse_type(s: &str) -> Option<ChannelType> {
+	Some(match s {
+		"f64" => ChannelType::F64,
+		"bool" => ChannelType::Bool,
+		"int" => ChannelType::Int,
+		"uint" => ChannelType::Uint,
+		"enum()" => ChannelType::Enum(Vec::new()),
+		_ => {
+			if let Some(variants) = s.strip_prefix("enum(") {
+				let variants = variants.strip_suffix(')')?.split('|');
+				ChannelType::Enum(variants.map(str::to_string).collect())
+			} else {
+				let max_len = s.strip_prefix("str(")?.strip_suffix(')')?;
+				ChannelType::Str(max_len.parse().ok()?)
+			}
+		},
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::RunData;
+
+	fn header(names: &[&str]) -> Header {
+		Header {
+			names: names.iter().map(|name| name.to_string()).collect(),
+			types: vec![ChannelType::F64; names.len()],
+			units: vec![String::new(); names.len()],
+			descriptions: vec![String::new(); names.len()],
+			interval: None,
+		}
+	}
+
+	#[test]
+	fn options_control_formatting() {
+		let path = std::env::temp_dir().join("simlib-csv-options.tsv");
+		let options = CsvOptions::tsv()
+			.notation(Notation::Fixed(3))
+			.nan("")
+			.infinity("Infinity");
+		let header = header(&["a\tb", "c\"d"]);
+		let mut writer = CsvWriter::new(File::create(&path).unwrap(), &header, &options).unwrap();
+		writer
+			.write_row(0.5, &[Value::F64(1.0 / 3.0), Value::F64(f64::NAN)])
+			.unwrap();
+		writer
+			.write_row(1.0, &[Value::F64(f64::NEG_INFINITY), Value::F64(2.0)])
+			.unwrap();
+		writer.finish().unwrap();
+
+		assert_eq!(
+			std::fs::read_to_string(&path).unwrap(),
+			"time\t\"a\tb\"\t\"c\"\"d\"\n0.500\t0.333\t\n1.000\t-Infinity\t2.000\n"
+		);
+		let data = RunData::from_csv_with(&path, &options).unwrap();
+		assert_eq!(data.names(), ["a\tb", "c\"d"]);
+		assert_eq!(data.column("a\tb").unwrap()[1], f64::NEG_INFINITY);
+		assert!(data.column("c\"d").unwrap()[0].is_nan());
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn shortest_notation_round_trips_exactly() {
+		let path = std::env::temp_dir().join("simlib-csv-exact.csv");
+		let values = [
+			0.1 + 0.2,
+			-0.0,
+			5e-324,
+			1e300,
+			f64::MAX,
+			f64::NAN,
+			f64::INFINITY,
+			1.0 / 3.0,
+		];
+		let mut data = RunData::new(&["x, y"]);
+		for (i, x) in values.iter().enumerate() {
+			data.push(i as f64 * 0.1, &[*x]);
+		}
+		data.save_csv(&path).unwrap();
+		let text = std::fs::read_to_string(&path).unwrap();
+		assert!(text.contains("\n0.2,5e-324\n0.30000000000000004,1e300\n"), "{text}");
+
+		let loaded = RunData::load(&path).unwrap();
+		let bits = |data: &RunData| -> Vec<u64> {
+			data.column("x, y")
+				.unwrap()
+				.iter()
+				.map(|x| x.to_bits())
+				.collect()
+		};
+		assert_eq!(bits(&loaded), bits(&data));
+		assert_eq!(loaded.time(), data.time());
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn enum_names_are_quoted() {
+		#[derive(Clone, Debug, PartialEq)]
+		enum Stage {
+			Idle,
+			Burn(u8, u8),
+		}
+
+		let path = std::env::temp_dir().join("simlib-csv-enum.csv");
+		let mut recorder = crate::Recorder::<f64>::new(&path);
+		let stage = |x: &f64| {
+			if *x > 0.0 {
+				Stage::Burn(1, 2)
+			} else {
+				Stage::Idle
+			}
+		};
+		recorder.track_enum("stage", &[Stage::Idle, Stage::Burn(1, 2)], stage);
+		recorder.track("x", |x| *x);
+		recorder.start().unwrap();
+		recorder.sample(&0.0, 0.0, []).unwrap();
+		recorder.sample(&1.0, 1.0, []).unwrap();
+		recorder.finish().unwrap();
+
+		let text = std::fs::read_to_string(&path).unwrap();
+		assert!(text.ends_with("1.0,\"Burn(1, 2)\",1.0\n"), "{text}");
+		let data = RunData::load(&path).unwrap();
+		assert_eq!(data.enum_name("stage", 1), Some("Burn(1, 2)"));
+		assert_eq!(data.column("x").unwrap(), [0.0, 1.0]);
+		std::fs::remove_file(&path).unwrap();
+		std::fs::remove_file(metadata_path(&path)).unwrap();
+	}
+}
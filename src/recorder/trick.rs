@@ -0,0 +1,387 @@
+//! Trick's binary data recording format (`.trk`), as written by Trick 10 and later.
+//!
+//! A file starts with the 10 byte string `Trick-10-L` (or `B` for big-endian data), followed by the
+//! number of parameters as an `int32`. Each parameter is described by its name and units, each an
+//! `int32` byte length followed by the characters, then its Trick type and size in bytes as
+//! `int32`s. The rows follow the header with no padding, each holding every parameter's value.
+//!
+//! The first parameter is the time, `sys.exec.out.time`, and unitless parameters have the units
+//! `--`. Recordings are written in little-endian order, with `f64`, `bool`, `i64` and `u64` channels
+//! as `TRICK_DOUBLE`, `TRICK_BOOLEAN`, `TRICK_LONG_LONG` and `TRICK_UNSIGNED_LONG_LONG`. Enums are
+//! written as `TRICK_ENUMERATED` indices, since the format has nowhere to store their names, and
+//! strings as `TRICK_CHARACTER` arrays of their maximum length. Channel descriptions are not
+//! written, since the format has nowhere to store them.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::value::encode;
+use super::{ChannelType, Header, RowWriter, Value};
+use crate::run_data::Column;
+
+/// Name of the time parameter in Trick recordings.
+pub const TRICK_TIME: &str = "sys.exec.out.time";
+
+// type codes from Trick's `parameter_types.h`
+const TRICK_CHARACTER: i32 = 1;
+const TRICK_UNSIGNED_CHARACTER: i32 = 2;
+const TRICK_SHORT: i32 = 4;
+const TRICK_UNSIGNED_SHORT: i32 = 5;
+const TRICK_INTEGER: i32 = 6;
+const TRICK_UNSIGNED_INTEGER: i32 = 7;
+const TRICK_LONG: i32 = 8;
+const TRICK_UNSIGNED_LONG: i32 = 9;
+const TRICK_FLOAT: i32 = 10;
+const TRICK_DOUBLE: i32 = 11;
+const TRICK_LONG_LONG: i32 = 14;
+const TRICK_UNSIGNED_LONG_LONG: i32 = 15;
+const TRICK_BOOLEAN: i32 = 17;
+const TRICK_ENUMERATED: i32 = 21;
+
+pub(crate) struct TrickWriter {
+	writer: BufWriter<File>,
+	types: Vec<ChannelType>,
+}
+
+impl TrickWriter {
+	pub(crate) fn new(file: File, header: &Header) -> io::Result<Self> {
+		let mut writer = BufWriter::new(file);
+		writer.write_all(b"Trick-10-L")?;
+		writer.write_all(&(header.names.len() as i32 + 1).to_le_bytes())?;
+
+		let units = header
+			.units
+			.iter()
+			.map(|unit| if unit.is_empty() { "--" } else { unit });
+		let parameters = header
+			.names
+			.iter()
+			.map(String::as_str)
+			.zip(units)
+			.zip(&header.types);
+		let time = ((TRICK_TIME, "s"), &ChannelType::F64);
+		for ((name, unit), ty) in std::iter::once(time).chain(parameters) {
+			let trick_type = match ty {
+				ChannelType::F64 => TRICK_DOUBLE,
+				ChannelType::Bool => TRICK_BOOLEAN,
+				ChannelType::Int => TRICK_LONG_LONG,
+				ChannelType::Uint => TRICK_UNSIGNED_LONG_LONG,
+				ChannelType::Enum(_) => TRICK_ENUMERATED,
+				ChannelType::Str(_) => TRICK_CHARACTER,
+			};
+			write_str(&mut writer, name)?;
+			write_str(&mut writer, unit)?;
+			writer.write_all(&trick_type.to_le_bytes())?;
+			writer.write_all(&(ty.size() as i32).to_le_bytes())?;
+		}
+
+		Ok(Self {
+			writer,
+			types: header.types.clone(),
+		})
+	}
+}
+
+impl RowWriter for TrickWriter {
+	fn write_row(&mut self, t: f64, row: &[Value]) -> io::Result<()> {
+		let mut bytes = t.to_le_bytes().to_vec();
+		for (x, ty) in row.iter().zip(&self.types) {
+			encode(x, ty, &mut bytes);
+		}
+		self.writer.write_all(&bytes)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.writer.flush()
+	}
+
+	fn finish(&mut self) -> io::Result<()> {
+		self.writer.flush()
+	}
+}
+
+fn write_str(writer: &mut impl Write, s: &str) -> io::Result<()> {
+	writer.write_all(&(s.len() as i32).to_le_bytes())?;
+	writer.write_all(s.as_bytes())
+}
+
+/// A parameter in a Trick recording.
+struct Parameter {
+	trick_type: i32,
+	ty: ChannelType,
+	offset: usize,
+}
+
+/// Reads Trick `.trk` recordings, such as those from legacy Trick runs.
+///
+/// Floating-point parameters are read as `f64`, integer and enumerated parameters as `i64` or
+/// `u64`, and booleans as `bool`. Character arrays are read as strings.
+pub struct TrickReader {
+	file: BufReader<File>,
+	big_endian: bool,
+	names: Vec<String>,
+	units: Vec<String>,
+	parameters: Vec<Parameter>,
+	row_size: usize,
+	data_start: u64,
+	rows: u64,
+}
+
+impl TrickReader {
+	pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		let mut file = BufReader::new(File::open(path)?);
+
+		let mut magic = [0; 10];
+		file.read_exact(&mut magic)?;
+		if !magic.starts_with(b"Trick-") || !magic[6..8].iter().all(u8::is_ascii_digit) {
+			return Err(invalid_data("not a Trick recording"));
+		}
+		if &magic[6..8] < b"10" {
+			return Err(invalid_data("recordings from before Trick 10 are not supported"));
+		}
+		let big_endian = match &magic[8..] {
+			b"-L" => false,
+			b"-B" => true,
+			_ => return Err(invalid_data("not a Trick recording")),
+		};
+
+		let count = read_i32(&mut file, big_endian)?;
+		let mut names = Vec::new();
+		let mut units = Vec::new();
+		let mut parameters = Vec::new();
+		let mut row_size = 0;
+		for _ in 0..count {
+			names.push(read_str(&mut file, big_endian, "parameter name")?);
+			units.push(read_str(&mut file, big_endian, "unit")?);
+			let trick_type = read_i32(&mut file, big_endian)?;
+			let size = usize::try_from(read_i32(&mut file, big_endian)?)
+				.map_err(|_| invalid_data("negative parameter size"))?;
+			parameters.push(Parameter {
+				trick_type,
+				ty: channel_type(trick_type, size)?,
+				offset: row_size,
+			});
+			row_size += size;
+		}
+
+		let data_start = file.stream_position()?;
+		let len = file.seek(SeekFrom::End(0))?;
+		let rows = match row_size {
+			0 => 0,
+			_ => (len - data_start) / row_size as u64,
+		};
+
+		Ok(Self {
+			file,
+			big_endian,
+			names,
+			units,
+			parameters,
+			row_size,
+			data_start,
+			rows,
+		})
+	}
+
+	/// Returns the parameter names, starting with [`TRICK_TIME`].
+	pub fn names(&self) -> &[String] {
+		&self.names
+	}
+
+	/// Returns the type each parameter is read as.
+	pub fn types(&self) -> Vec<ChannelType> {
+		self.parameters.iter().map(|p| p.ty.clone()).collect()
+	}
+
+	pub fn units(&self) -> &[String] {
+		&self.units
+	}
+
+	/// Returns the number of complete rows.
+	pub fn len(&self) -> u64 {
+		self.rows
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.rows == 0
+	}
+
+	/// Reads one row.
+	///
+	/// Returns an error of kind [`io::ErrorKind::InvalidInput`] if there is no such row.
+	pub fn read_row(&mut self, row: u64) -> io::Result<Vec<Value>> {
+		if row >= self.rows {
+			return Err(row_out_of_range(row, self.rows));
+		}
+		let start = self.data_start + row * self.row_size as u64;
+		self.file.seek(SeekFrom::Start(start))?;
+		let mut bytes = vec![0; self.row_size];
+		self.file.read_exact(&mut bytes)?;
+		Ok(self.decode_row(&bytes).collect())
+	}
+
+	/// Reads every row into one column per parameter.
+	pub fn read_columns(&mut self) -> io::Result<Vec<Column>> {
+		let mut columns: Vec<Column> = self.parameters.iter().map(|p| Column::new(&p.ty)).collect();
+		self.file.seek(SeekFrom::Start(self.data_start))?;
+		let mut bytes = vec![0; self.row_size];
+		for _ in 0..self.rows {
+			self.file.read_exact(&mut bytes)?;
+			for (column, x) in columns.iter_mut().zip(self.decode_row(&bytes)) {
+				column.push(x);
+			}
+		}
+		Ok(columns)
+	}
+
+	fn decode_row<'a>(&'a self, bytes: &'a [u8]) -> impl Iterator<Item = Value> + 'a {
+		self.parameters.iter().map(|p| {
+			let bytes = &bytes[p.offset..p.offset + p.ty.size()];
+			decode(p.trick_type, &p.ty, bytes, self.big_endian)
+		})
+	}
+}
+
+/// Returns the type a Trick parameter is read as.
+fn channel_type(trick_type: i32, size: usize) -> io::Result<ChannelType> {
+	Ok(match (trick_type, size) {
+		(TRICK_DOUBLE, 8) | (TRICK_FLOAT, 4) => ChannelType::F64,
+		(TRICK_BOOLEAN, 1..=8) => ChannelType::Bool,
+		(TRICK_CHARACTER | TRICK_UNSIGNED_CHARACTER, 2..) => ChannelType::Str(size),
+		(
+			TRICK_CHARACTER | TRICK_SHORT | TRICK_INTEGER | TRICK_LONG | TRICK_LONG_LONG
+			| TRICK_ENUMERATED,
+			1..=8,
+		) => ChannelType::Int,
+		(
+			TRICK_UNSIGNED_CHARACTER
+			| TRICK_UNSIGNED_SHORT
+			| TRICK_UNSIGNED_INTEGER
+			| TRICK_UNSIGNED_LONG
+			| TRICK_UNSIGNED_LONG_LONG,
+			1..=8,
+		) => ChannelType::Uint,
+		(trick_type, size) => {
+			return Err(invalid_data(&format!(
+				"unsupported Trick type {trick_type} with size {size}"
+			)));
+		},
+	})
+}
+
+/// Reads a value of a Trick parameter.
+fn decode(trick_type: i32, ty: &ChannelType, bytes: &[u8], big_endian: bool) -> Value {
+	if let ChannelType::Str(_) = ty {
+		let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+		return Value::Str(String::from_utf8_lossy(&bytes[..len]).into_owned());
+	}
+
+	let n = bytes.len();
+	let mut buf = [0; 8];
+	buf[..n].copy_from_slice(bytes);
+	if big_endian {
+		buf[..n].reverse();
+	}
+	let unsigned = u64::from_le_bytes(buf);
+
+	match ty {
+		ChannelType::F64 if trick_type == TRICK_FLOAT => {
+			Value::F64(f32::from_bits(unsigned as u32) as f64)
+		},
+		ChannelType::F64 => Value::F64(f64::from_bits(unsigned)),
+		ChannelType::Bool => Value::Bool(unsigned != 0),
+		// sign-extend values narrower than 8 bytes
+		ChannelType::Int => Value::Int(((unsigned << (64 - 8 * n)) as i64) >> (64 - 8 * n)),
+		_ => Value::Uint(unsigned),
+	}
+}
+
+fn read_i32(reader: &mut impl Read, big_endian: bool) -> io::Result<i32> {
+	let mut bytes = [0; 4];
+	reader.read_exact(&mut bytes)?;
+	Ok(if big_endian {
+		i32::from_be_bytes(bytes)
+	} else {
+		i32::from_le_bytes(bytes)
+	})
+}
+
+/// Reads a string, naming what it holds in the error if it is not UTF-8.
+fn read_str(reader: &mut impl Read, big_endian: bool, what: &str) -> io::Result<String> {
+	let len = usize::try_from(read_i32(reader, big_endian)?)
+		.map_err(|_| invalid_data("negative string length"))?;
+	let mut bytes = vec![0; len];
+	reader.read_exact(&mut bytes)?;
+	String::from_utf8(bytes).map_err(|_| invalid_data(&format!("{what} is not UTF-8")))
+}
+
+fn row_out_of_range(row: u64, rows: u64) -> io::Error {
+	io::Error::new(
+		io::ErrorKind::InvalidInput,
+		format!("row {row} is out of range for a recording of {rows} rows"),
+	)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn recording_round_trips() {
+		let path = std::env::temp_dir().join("simlib-trick.trk");
+		let header = Header {
+			names: ["ball.pos", "ball.mass", "ball.bounces", "ball.name"]
+				.map(String::from)
+				.to_vec(),
+			types: vec![
+				ChannelType::F64,
+				ChannelType::F64,
+				ChannelType::Int,
+				ChannelType::Str(8),
+			],
+			units: vec!["m".to_string(), String::new(), String::new(), String::new()],
+			descriptions: vec![String::new(); 4],
+			interval: None,
+		};
+		let row = |pos, bounces| {
+			let name = Value::Str("red".to_string());
+			[Value::F64(pos), Value::F64(2.0), Value::Int(bounces), name]
+		};
+		let mut writer = TrickWriter::new(File::create(&path).unwrap(), &header).unwrap();
+		writer.write_row(0.0, &row(1.0, 0)).unwrap();
+		writer.write_row(0.1, &row(1.5, -1)).unwrap();
+		writer.finish().unwrap();
+
+		let bytes = std::fs::read(&path).unwrap();
+		assert_eq!(&bytes[..14], b"Trick-10-L\x05\0\0\0");
+
+		let mut reader = TrickReader::open(&path).unwrap();
+		assert_eq!(reader.names()[..3], [TRICK_TIME, "ball.pos", "ball.mass"]);
+		assert_eq!(reader.units()[..3], ["s", "m", "--"]);
+		assert_eq!(reader.types()[3..], [ChannelType::Int, ChannelType::Str(8)]);
+		assert_eq!(reader.len(), 2);
+		assert_eq!(reader.read_row(1).unwrap()[1..], row(1.5, -1));
+		assert_eq!(reader.read_row(2).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+		assert_eq!(reader.read_columns().unwrap()[1], Column::Numeric(vec![1.0, 1.5]));
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn decodes_trick_types() {
+		let decode = |trick_type, bytes: &[u8], big_endian| {
+			let ty = channel_type(trick_type, bytes.len()).unwrap();
+			decode(trick_type, &ty, bytes, big_endian)
+		};
+		assert_eq!(decode(TRICK_INTEGER, &(-3_i32).to_le_bytes(), false), Value::Int(-3));
+		assert_eq!(decode(TRICK_INTEGER, &(-3_i32).to_be_bytes(), true), Value::Int(-3));
+		assert_eq!(decode(TRICK_UNSIGNED_SHORT, &[0xff, 0xff], false), Value::Uint(65535));
+		assert_eq!(decode(TRICK_FLOAT, &1.5_f32.to_be_bytes(), true), Value::F64(1.5));
+		assert_eq!(decode(TRICK_BOOLEAN, &[1], false), Value::Bool(true));
+		assert!(channel_type(3, 8).is_err());
+	}
+}
@@ -0,0 +1,144 @@
+//! Typed values of recorded channels.
+
+use std::fmt;
+
+/// The type of a recorded channel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChannelType {
+	F64,
+	Bool,
+	Int,
+	Uint,
+	/// An enum recorded as the index of its value in the variant names.
+	Enum(Vec<String>),
+	/// A string of at most the given number of bytes.
+	Str(usize),
+}
+
+impl ChannelType {
+	/// Returns true if the channel's values can be converted to `f64`.
+	pub fn is_numeric(&self) -> bool {
+		!matches!(self, Self::Str(_))
+	}
+
+	/// Returns the number of bytes a value takes in the binary formats.
+	pub(crate) fn size(&self) -> usize {
+		match self {
+			Self::F64 | Self::Int | Self::Uint => 8,
+			Self::Bool => 1,
+			Self::Enum(_) => 4,
+			Self::Str(max_len) => *max_len,
+		}
+	}
+}
+
+/// A sample of a recorded channel.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+	F64(f64),
+	Bool(bool),
+	Int(i64),
+	Uint(u64),
+	/// The index of an enum value in its channel's variant names.
+	Enum(u32),
+	Str(String),
+}
+
+impl Value {
+	/// Converts the value to `f64`, with booleans as 0 or 1, enums as their index and strings as
+	/// NaN.
+	pub fn as_f64(&self) -> f64 {
+		match self {
+			Self::F64(x) => *x,
+			Self::Bool(b) => f64::from(u8::from(*b)),
+			Self::Int(i) => *i as f64,
+			Self::Uint(u) => *u as f64,
+			Self::Enum(index) => f64::from(*index),
+			Self::Str(_) => f64::NAN,
+		}
+	}
+
+	/// Converts a value from its `f64` form back to the given type.
+	///
+	/// Returns `None` for strings, which have no `f64` form.
+	pub fn from_f64(x: f64, ty: &ChannelType) -> Option<Self> {
+		Some(match ty {
+			ChannelType::F64 => Self::F64(x),
+			ChannelType::Bool => Self::Bool(x != 0.0),
+			ChannelType::Int => Self::Int(x as i64),
+			ChannelType::Uint => Self::Uint(x as u64),
+			ChannelType::Enum(_) => Self::Enum(x as u32),
+			ChannelType::Str(_) => return None,
+		})
+	}
+
+	/// Formats the value, rendering enums with their variant names.
+	pub(crate) fn display<'a>(&'a self, ty: &'a ChannelType) -> impl fmt::Display + 'a {
+		DisplayValue { value: self, ty }
+	}
+}
+
+struct DisplayValue<'a> {
+	value: &'a Value,
+	ty: &'a ChannelType,
+}
+
+impl fmt::Display for DisplayValue<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match (self.value, self.ty) {
+			(Value::Enum(index), ChannelType::Enum(names)) => match names.get(*index as usize) {
+				Some(name) => write!(f, "{name}"),
+				None => write!(f, "{index}"),
+			},
+			(Value::F64(x), _) => write!(f, "{x}"),
+			(Value::Bool(b), _) => write!(f, "{b}"),
+			(Value::Int(i), _) => write!(f, "{i}"),
+			(Value::Uint(u), _) => write!(f, "{u}"),
+			(Value::Enum(index), _) => write!(f, "{index}"),
+			(Value::Str(s), _) => write!(f, "{s}"),
+		}
+	}
+}
+
+/// Shortens `s` to at most `max_len` bytes without splitting a character.
+pub(crate) fn truncate(s: &mut String, max_len: usize) {
+	if s.len() > max_len {
+		let end = (0..=max_len)
+			.rev()
+			.find(|&i| s.is_char_boundary(i))
+			.unwrap();
+		s.truncate(end);
+	}
+}
+
+/// Appends the little-endian binary form of `value`, zero-padding strings to their maximum length.
+pub(crate) fn encode(value: &Value, ty: &ChannelType, bytes: &mut Vec<u8>) {
+	match value {
+		Value::F64(x) => bytes.extend_from_slice(&x.to_le_bytes()),
+		Value::Bool(b) => bytes.push(u8::from(*b)),
+		Value::Int(i) => bytes.extend_from_slice(&i.to_le_bytes()),
+		Value::Uint(u) => bytes.extend_from_slice(&u.to_le_bytes()),
+		Value::Enum(index) => bytes.extend_from_slice(&index.to_le_bytes()),
+		Value::Str(s) => {
+			let start = bytes.len();
+			bytes.extend_from_slice(s.as_bytes());
+			bytes.resize(start + ty.size(), 0);
+		},
+	}
+}
+
+/// Reads a value from its little-endian binary form.
+pub(crate) fn decode(bytes: &[u8], ty: &ChannelType) -> Value {
+	let array = |bytes: &[u8]| -> [u8; 8] { bytes[..8].try_into().unwrap() };
+	match ty {
+		ChannelType::F64 => Value::F64(f64::from_le_bytes(array(bytes))),
+		ChannelType::Bool => Value::Bool(bytes[0] != 0),
+		ChannelType::Int => Value::Int(i64::from_le_bytes(array(bytes))),
+		ChannelType::Uint => Value::Uint(u64::from_le_bytes(array(bytes))),
+		ChannelType::Enum(_) => Value::Enum(u32::from_le_bytes(bytes[..4].try_into().unwrap())),
+		ChannelType::Str(_) => {
+			let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+			Value::Str(String::from_utf8_lossy(&bytes[..len]).into_owned())
+		},
+	}
+}
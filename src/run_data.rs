@@ -0,0 +1,685 @@
+//! Loading and querying recorded runs.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+use crate::recorder::{
+	ChannelMetadata, ChannelType, CsvOptions, CsvWriter, Header, RecordingReader, RowWriter,
+	TrickReader, Value, read_metadata, write_metadata,
+};
+use crate::units::{Quantity, UnitError};
+
+/// The samples of one channel.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Column {
+	/// Values of every type except strings, converted with [`Value::as_f64`].
+	Numeric(Vec<f64>),
+	Text(Vec<String>),
+}
+
+impl Column {
+	pub(crate) fn new(ty: &ChannelType) -> Self {
+		if ty.is_numeric() {
+			Self::Numeric(Vec::new())
+		} else {
+			Self::Text(Vec::new())
+		}
+	}
+
+	pub(crate) fn push(&mut self, value: Value) {
+		match (self, value) {
+			(Self::Text(column), Value::Str(s)) => column.push(s),
+			(Self::Text(column), value) => column.push(value.as_f64().to_string()),
+			(Self::Numeric(column), value) => column.push(value.as_f64()),
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		match self {
+			Self::Numeric(column) => column.len(),
+			Self::Text(column) => column.len(),
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	pub fn as_numeric(&self) -> Option<&[f64]> {
+		match self {
+			Self::Numeric(column) => Some(column),
+			Self::Text(_) => None,
+		}
+	}
+
+	pub fn as_text(&self) -> Option<&[String]> {
+		match self {
+			Self::Numeric(_) => None,
+			Self::Text(column) => Some(column),
+		}
+	}
+
+	fn slice(&self, first: usize, last: usize) -> Self {
+		match self {
+			Self::Numeric(column) => Self::Numeric(column[first..last].to_vec()),
+			Self::Text(column) => Self::Text(column[first..last].to_vec()),
+		}
+	}
+}
+
+/// The recorded time histories of one run, held in memory as one column per channel.
+///
+/// Every query takes a channel name and returns `None` if the run has no channel with that name,
+/// or if it is a string channel and the query needs numbers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunData {
+	names: Vec<String>,
+	types: Vec<ChannelType>,
+	units: Vec<String>,
+	descriptions: Vec<String>,
+	time: Vec<f64>,
+	columns: Vec<Column>,
+}
+
+impl RunData {
+	/// Creates an empty run with the given `f64` channels, which have no units or descriptions.
+	pub fn new(names: &[&str]) -> Self {
+		Self::with_types(
+			names.iter().map(|name| name.to_string()).collect(),
+			vec![ChannelType::F64; names.len()],
+			vec![String::new(); names.len()],
+			vec![String::new(); names.len()],
+		)
+	}
+
+	pub(crate) fn with_types(
+		names: Vec<String>,
+		types: Vec<ChannelType>,
+		units: Vec<String>,
+		descriptions: Vec<String>,
+	) -> Self {
+		let columns = types.iter().map(Column::new).collect();
+		Self {
+			names,
+			types,
+			units,
+			descriptions,
+			time: Vec::new(),
+			columns,
+		}
+	}
+
+	/// Loads a recording, detecting whether it is CSV, simlib binary or Trick `.trk`.
+	pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		let mut magic = [0; 8];
+		let n = File::open(&path)?.read(&mut magic)?;
+		match &magic[..n] {
+			// every version goes to the binary reader, which rejects those it cannot read
+			[b'S', b'I', b'M', b'R', b'E', b'C', ..] => Self::from_binary(path),
+			[b'T', b'r', b'i', b'c', b'k', b'-', ..] => Self::from_trick(path),
+			_ => Self::from_csv(path),
+		}
+	}
+
+	/// Loads a CSV recording whose first column is time, written with the default [`CsvOptions`]
+	/// or with tabs between fields.
+	///
+	/// Types, units and descriptions are read from the recording's `.meta.csv` sidecar file, if it
+	/// has one. A column the sidecar does not describe is read as `f64` if every value is a number,
+	/// `bool` if every value is `true` or `false`, and as strings otherwise.
+	pub fn from_csv<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		let mut header = String::new();
+		BufReader::new(File::open(&path)?).read_line(&mut header)?;
+		let options = if !header.contains(',') && header.contains('\t') {
+			CsvOptions::tsv()
+		} else {
+			CsvOptions::new()
+		};
+		Self::from_csv_with(path, &options)
+	}
+
+	/// Loads a CSV recording whose first column is time, written with the given options.
+	///
+	/// Numbers are also read if they are in Rust's own format, whatever the notation and
+	/// representation of non-finite numbers in the options.
+	pub fn from_csv_with<P: AsRef<Path>>(path: P, options: &CsvOptions) -> io::Result<Self> {
+		let path = path.as_ref();
+		let mut lines = BufReader::new(File::open(path)?).lines();
+		let header = lines
+			.next()
+			.ok_or_else(|| invalid_data("empty CSV file"))??;
+		let names: Vec<String> = options.split_fields(&header).into_iter().skip(1).collect();
+
+		let mut time = Vec::new();
+		let mut fields: Vec<Vec<String>> = vec![Vec::new(); names.len()];
+		for (i, line) in lines.enumerate() {
+			let line = line?;
+			if line.is_empty() {
+				continue;
+			}
+			let row = options.split_fields(&line);
+			if row.len() != names.len() + 1 {
+				return Err(invalid_data(&format!("line {}: wrong number of columns", i + 2)));
+			}
+			let t = options
+				.parse_number(&row[0])
+				.ok_or_else(|| invalid_data(&format!("line {}: invalid time", i + 2)))?;
+			time.push(t);
+			for (column, field) in fields.iter_mut().zip(row.into_iter().skip(1)) {
+				column.push(field);
+			}
+		}
+
+		let mut metadata: Vec<Option<ChannelMetadata>> = names.iter().map(|_| None).collect();
+		for channel in read_metadata(path)?.unwrap_or_default() {
+			if let Some(i) = names.iter().position(|n| *n == channel.name) {
+				metadata[i] = Some(channel);
+			}
+		}
+
+		let mut types = Vec::new();
+		let mut units = Vec::new();
+		let mut descriptions = Vec::new();
+		let mut columns = Vec::new();
+		for ((name, column), metadata) in names.iter().zip(fields).zip(metadata) {
+			let (ty, column) = match metadata {
+				Some(metadata) => {
+					units.push(metadata.unit);
+					descriptions.push(metadata.description);
+					let column = parse_column(column, &metadata.ty, options).ok_or_else(|| {
+						invalid_data(&format!("column {name} has values that are not its type"))
+					})?;
+					(metadata.ty, column)
+				},
+				None => {
+					units.push(String::new());
+					descriptions.push(String::new());
+					infer_column(column, options)
+				},
+			};
+			types.push(ty);
+			columns.push(column);
+		}
+		Ok(Self {
+			names,
+			types,
+			units,
+			descriptions,
+			time,
+			columns,
+		})
+	}
+
+	/// Loads a recording in simlib's binary format.
+	pub fn from_binary<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		let mut reader = RecordingReader::open(path)?;
+		let names = reader.names()[1..].to_vec();
+		let types = reader.types()[1..].to_vec();
+		let units = reader.units()[1..].to_vec();
+		let descriptions = reader.descriptions()[1..].to_vec();
+		let columns = reader.read_columns()?;
+		Ok(Self::from_parts(names, types, units, descriptions, columns))
+	}
+
+	/// Loads a Trick `.trk` recording whose first parameter is time.
+	///
+	/// Parameters with Trick's unitless units, `--`, have no unit, and no parameter has a
+	/// description.
+	pub fn from_trick<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		let mut reader = TrickReader::open(path)?;
+		if reader.names().is_empty() {
+			return Err(invalid_data("Trick recording has no time parameter"));
+		}
+		if reader.types()[0] != ChannelType::F64 {
+			return Err(invalid_data("Trick recording time is not a number"));
+		}
+		let names = reader.names()[1..].to_vec();
+		let types = reader.types()[1..].to_vec();
+		let units = reader.units()[1..]
+			.iter()
+			.map(|unit| {
+				if unit == "--" {
+					String::new()
+				} else {
+					unit.clone()
+				}
+			})
+			.collect();
+		let descriptions = vec![String::new(); names.len()];
+		let columns = reader.read_columns()?;
+		Ok(Self::from_parts(names, types, units, descriptions, columns))
+	}
+
+	/// Builds a run from columns that start with time.
+	fn from_parts(
+		names: Vec<String>,
+		types: Vec<ChannelType>,
+		units: Vec<String>,
+		descriptions: Vec<String>,
+		mut columns: Vec<Column>,
+	) -> Self {
+		let Column::Numeric(time) = columns.remove(0) else {
+			unreachable!("time is always numeric");
+		};
+		Self {
+			names,
+			types,
+			units,
+			descriptions,
+			time,
+			columns,
+		}
+	}
+
+	/// Saves the run as a CSV recording, with its units and descriptions in a sidecar file.
+	pub fn save_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+		self.save_csv_with(path, &CsvOptions::new())
+	}
+
+	/// Saves the run as a CSV recording written with the given options.
+	pub fn save_csv_with<P: AsRef<Path>>(&self, path: P, options: &CsvOptions) -> io::Result<()> {
+		let path = path.as_ref();
+		let header = Header {
+			names: self.names.clone(),
+			types: self.types.clone(),
+			units: self.units.clone(),
+			descriptions: self.descriptions.clone(),
+			interval: None,
+		};
+		write_metadata(path, &header)?;
+		let mut writer = CsvWriter::new(File::create(path)?, &header, options)?;
+		for (i, &t) in self.time.iter().enumerate() {
+			let row: Vec<Value> = (0..self.names.len())
+				.map(|channel| self.channel_value(channel, i).unwrap())
+				.collect();
+			writer.write_row(t, &row)?;
+		}
+		writer.finish()
+	}
+
+	/// Appends a sample of numbers.
+	pub fn push(&mut self, t: f64, row: &[f64]) {
+		let values: Vec<Value> = row.iter().map(|x| Value::F64(*x)).collect();
+		self.push_values(t, &values);
+	}
+
+	/// Appends a sample.
+	pub fn push_values(&mut self, t: f64, row: &[Value]) {
+		assert_eq!(row.len(), self.columns.len(), "row does not match the channels");
+		self.time.push(t);
+		for (column, x) in self.columns.iter_mut().zip(row) {
+			column.push(x.clone());
+		}
+	}
+
+	/// Returns the channel names, not including time.
+	pub fn names(&self) -> &[String] {
+		&self.names
+	}
+
+	pub fn types(&self) -> &[ChannelType] {
+		&self.types
+	}
+
+	/// Returns the unit of each channel, which is empty if it was not given.
+	pub fn units(&self) -> &[String] {
+		&self.units
+	}
+
+	/// Returns the description of each channel, which is empty if it was not given.
+	pub fn descriptions(&self) -> &[String] {
+		&self.descriptions
+	}
+
+	/// Returns the unit of a channel, which is empty if it was not given.
+	pub fn unit(&self, name: &str) -> Option<&str> {
+		let index = self.names.iter().position(|n| n == name)?;
+		Some(&self.units[index])
+	}
+
+	pub fn description(&self, name: &str) -> Option<&str> {
+		let index = self.names.iter().position(|n| n == name)?;
+		Some(&self.descriptions[index])
+	}
+
+	/// Returns a label for a channel, such as an axis label, made of its name and its unit in
+	/// brackets, like `altitude [m]`.
+	pub fn label(&self, name: &str) -> Option<String> {
+		match self.unit(name)? {
+			"" => Some(name.to_string()),
+			unit => Some(format!("{name} [{unit}]")),
+		}
+	}
+
+	/// Returns the sample times.
+	pub fn time(&self) -> &[f64] {
+		&self.time
+	}
+
+	/// Returns the number of samples.
+	pub fn len(&self) -> usize {
+		self.time.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.time.is_empty()
+	}
+
+	/// Returns the values of a numeric channel.
+	pub fn column(&self, name: &str) -> Option<&[f64]> {
+		self.typed_column(name)?.as_numeric()
+	}
+
+	/// Returns the values of a string channel.
+	pub fn text(&self, name: &str) -> Option<&[String]> {
+		self.typed_column(name)?.as_text()
+	}
+
+	/// Returns the values of a numeric channel as quantities, converted from its recorded unit.
+	///
+	/// Fails if the channel's unit is not a unit of `Q`, including if it has no unit.
+	pub fn quantities<Q: Quantity>(&self, name: &str) -> Option<Result<Vec<Q>, UnitError>> {
+		let unit = self.unit(name)?;
+		let column = self.column(name)?;
+		Some(column.iter().map(|x| Q::from_unit(*x, unit)).collect())
+	}
+
+	/// Returns the value of a channel at a sample index, with its recorded type.
+	pub fn value(&self, name: &str, index: usize) -> Option<Value> {
+		let channel = self.names.iter().position(|n| n == name)?;
+		self.channel_value(channel, index)
+	}
+
+	fn channel_value(&self, channel: usize, index: usize) -> Option<Value> {
+		match &self.columns[channel] {
+			Column::Numeric(column) => Value::from_f64(*column.get(index)?, &self.types[channel]),
+			Column::Text(column) => Some(Value::Str(column.get(index)?.clone())),
+		}
+	}
+
+	/// Returns the name of an enum channel's value at a sample index.
+	pub fn enum_name(&self, name: &str, index: usize) -> Option<&str> {
+		let channel = self.names.iter().position(|n| n == name)?;
+		let ChannelType::Enum(variants) = &self.types[channel] else {
+			return None;
+		};
+		let value = *self.columns[channel].as_numeric()?.get(index)?;
+		variants.get(value as usize).map(String::as_str)
+	}
+
+	fn typed_column(&self, name: &str) -> Option<&Column> {
+		let index = self.names.iter().position(|n| n == name)?;
+		Some(&self.columns[index])
+	}
+
+	/// Returns the samples between `start` and `end`, inclusive.
+	pub fn slice(&self, start: f64, end: f64) -> Self {
+		let first = self.time.partition_point(|t| *t < start);
+		let last = self.time.partition_point(|t| *t <= end).max(first);
+		Self {
+			names: self.names.clone(),
+			types: self.types.clone(),
+			units: self.units.clone(),
+			descriptions: self.descriptions.clone(),
+			time: self.time[first..last].to_vec(),
+			columns: self.columns.iter().map(|c| c.slice(first, last)).collect(),
+		}
+	}
+
+	/// Returns the value of a channel at time `t`, interpolating linearly between samples.
+	///
+	/// Returns `None` if `t` is outside the recorded times.
+	pub fn value_at(&self, name: &str, t: f64) -> Option<f64> {
+		let column = self.column(name)?;
+		let i = self.time.partition_point(|time| *time < t);
+		if i == self.time.len() {
+			return None;
+		}
+		if self.time[i] == t {
+			return Some(column[i]);
+		}
+		if i == 0 {
+			return None;
+		}
+
+		let (t0, t1) = (self.time[i - 1], self.time[i]);
+		let fraction = (t - t0) / (t1 - t0);
+		Some(column[i - 1] + fraction * (column[i] - column[i - 1]))
+	}
+
+	/// Returns every numeric channel interpolated at the given times, which must be within the
+	/// recorded times.
+	pub fn resample(&self, times: &[f64]) -> Option<Self> {
+		let numeric: Vec<usize> = (0..self.names.len())
+			.filter(|&i| self.types[i].is_numeric())
+			.collect();
+		let mut data = Self::with_types(
+			numeric.iter().map(|&i| self.names[i].clone()).collect(),
+			vec![ChannelType::F64; numeric.len()],
+			numeric.iter().map(|&i| self.units[i].clone()).collect(),
+			numeric
+				.iter()
+				.map(|&i| self.descriptions[i].clone())
+				.collect(),
+		);
+		for &t in times {
+			let row = numeric
+				.iter()
+				.map(|&i| self.value_at(&self.names[i], t))
+				.collect::<Option<Vec<f64>>>()?;
+			data.push(t, &row);
+		}
+		Some(data)
+	}
+
+	/// Returns the largest value of a channel, ignoring NaN.
+	pub fn max(&self, name: &str) -> Option<f64> {
+		self.extreme(name, |a, b| a > b).map(|(_, value)| value)
+	}
+
+	/// Returns the smallest value of a channel, ignoring NaN.
+	pub fn min(&self, name: &str) -> Option<f64> {
+		self.extreme(name, |a, b| a < b).map(|(_, value)| value)
+	}
+
+	/// Returns the first time a channel reaches its largest value.
+	pub fn time_of_max(&self, name: &str) -> Option<f64> {
+		self.extreme(name, |a, b| a > b).map(|(t, _)| t)
+	}
+
+	/// Returns the first time a channel reaches its smallest value.
+	pub fn time_of_min(&self, name: &str) -> Option<f64> {
+		self.extreme(name, |a, b| a < b).map(|(t, _)| t)
+	}
+
+	/// Returns the time and value of the first sample that no other sample is `better` than.
+	fn extreme(&self, name: &str, better: impl Fn(f64, f64) -> bool) -> Option<(f64, f64)> {
+		let column = self.column(name)?;
+		self.time
+			.iter()
+			.zip(column)
+			.filter(|(_, value)| !value.is_nan())
+			.fold(None, |best, (&t, &value)| match best {
+				Some((_, best_value)) if !better(value, best_value) => best,
+				_ => Some((t, value)),
+			})
+	}
+}
+
+/// Reads a CSV column of a known type, or returns `None` if a value is not of that type.
+fn parse_column(fields: Vec<String>, ty: &ChannelType, options: &CsvOptions) -> Option<Column> {
+	if let ChannelType::Str(_) = ty {
+		return Some(Column::Text(fields));
+	}
+	let values = fields.iter().map(|field| {
+		let field = field.trim();
+		Some(match ty {
+			ChannelType::F64 => options.parse_number(field)?,
+			ChannelType::Bool => f64::from(u8::from(field.parse::<bool>().ok()?)),
+			ChannelType::Int => field.parse::<i64>().ok()? as f64,
+			ChannelType::Uint => field.parse::<u64>().ok()? as f64,
+			// enums are written by name, or by index if the index has no name
+			ChannelType::Enum(variants) => match variants.iter().position(|v| v == field) {
+				Some(index) => index as f64,
+				None => f64::from(field.parse::<u32>().ok()?),
+			},
+			ChannelType::Str(_) => unreachable!(),
+		})
+	});
+	Some(Column::Numeric(values.collect::<Option<_>>()?))
+}
+
+/// Picks the type of a CSV column from its values.
+fn infer_column(fields: Vec<String>, options: &CsvOptions) -> (ChannelType, Column) {
+	let fields: Vec<String> = fields.into_iter().map(|f| f.trim().to_string()).collect();
+	if let Some(values) = fields.iter().map(|f| options.parse_number(f)).collect() {
+		return (ChannelType::F64, Column::Numeric(values));
+	}
+	if let Ok(values) = fields
+		.iter()
+		.map(|f| f.parse::<bool>())
+		.collect::<Result<Vec<_>, _>>()
+	{
+		let values = values.into_iter().map(|b| f64::from(u8::from(b))).collect();
+		return (ChannelType::Bool, Column::Numeric(values));
+	}
+	let max_len = fields.iter().map(String::len).max().unwrap_or(0);
+	(ChannelType::Str(max_len), Column::Text(fields))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn run() -> RunData {
+		let mut data = RunData::new(&["x", "y"]);
+		for (t, x) in [
+			(0.0, 1.0),
+			(1.0, 3.0),
+			(2.0, f64::NAN),
+			(3.0, 3.0),
+			(4.0, -2.0),
+		] {
+			data.push(t, &[x, 10.0 * t]);
+		}
+		data
+	}
+
+	#[test]
+	fn queries() {
+		let data = run();
+		assert_eq!(data.max("x"), Some(3.0));
+		assert_eq!(data.time_of_max("x"), Some(1.0));
+		assert_eq!(data.min("x"), Some(-2.0));
+		assert_eq!(data.time_of_min("x"), Some(4.0));
+		assert_eq!(data.max("z"), None);
+
+		assert_eq!(data.value_at("y", 2.25), Some(22.5));
+		assert_eq!(data.value_at("y", 4.0), Some(40.0));
+		assert_eq!(data.value_at("y", 4.5), None);
+		assert_eq!(data.value_at("y", -0.5), None);
+
+		let slice = data.slice(0.5, 3.0);
+		assert_eq!(slice.time(), [1.0, 2.0, 3.0]);
+		assert_eq!(slice.column("y"), Some(&[10.0, 20.0, 30.0][..]));
+	}
+
+	#[test]
+	fn loads_csv() {
+		let path = std::env::temp_dir().join("simlib-run-data.csv");
+		std::fs::write(&path, "time,x,y\n0,1,0\n1,3,10\n2,NaN,20\n3,3,30\n4,-2,40\n").unwrap();
+
+		let data = RunData::load(&path).unwrap();
+		assert_eq!(data.names(), ["x", "y"]);
+		assert_eq!(data.column("y"), run().column("y"));
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn reads_csv_metadata_from_sidecar() {
+		let path = std::env::temp_dir().join("simlib-run-data-meta.csv");
+		let mut recorder = crate::Recorder::<f64>::new(&path);
+		recorder
+			.track("altitude", |x| *x)
+			.unit("m")
+			.description("height, above ground");
+		recorder.track("count", |x| x.floor());
+		recorder.start().unwrap();
+		recorder.sample(&2.5, 0.0, []).unwrap();
+		recorder.finish().unwrap();
+
+		let data = RunData::load(&path).unwrap();
+		assert_eq!(data.units(), ["m", ""]);
+		assert_eq!(data.description("altitude"), Some("height, above ground"));
+		assert_eq!(data.label("altitude").unwrap(), "altitude [m]");
+		assert_eq!(data.label("count").unwrap(), "count");
+		let altitude = data.quantities::<crate::Length>("altitude").unwrap();
+		assert_eq!(altitude.unwrap()[0].as_feet(), 2.5 / 0.3048);
+		assert!(data.quantities::<crate::Length>("count").unwrap().is_err());
+		std::fs::remove_file(&path).unwrap();
+		std::fs::remove_file(path.with_extension("meta.csv")).unwrap();
+	}
+
+	#[test]
+	fn reads_csv_types_from_sidecar() {
+		#[derive(Clone, Debug, PartialEq)]
+		enum Mode {
+			Idle,
+			Boost,
+		}
+
+		let path = std::env::temp_dir().join("simlib-run-data-typed.csv");
+		let mut recorder = crate::Recorder::<f64>::new(&path);
+		recorder.track("x", |x| *x);
+		recorder.track_bool("armed", |x| *x > 0.0);
+		recorder.track_int("offset", |x| -(*x as i64));
+		recorder.track_uint("count", |x| *x as u64);
+		let mode = |x: &f64| if *x > 0.0 { Mode::Boost } else { Mode::Idle };
+		recorder.track_enum("mode", &[Mode::Idle, Mode::Boost], mode);
+		// strings that look like numbers or booleans are still read as strings
+		recorder.track_string("label", 8, |x| (*x > 0.0).to_string());
+		recorder.start().unwrap();
+		recorder.sample(&0.0, 0.0, []).unwrap();
+		recorder.sample(&2.0, 1.0, []).unwrap();
+		recorder.finish().unwrap();
+
+		let data = RunData::load(&path).unwrap();
+		assert_eq!(
+			data.types(),
+			[
+				ChannelType::F64,
+				ChannelType::Bool,
+				ChannelType::Int,
+				ChannelType::Uint,
+				ChannelType::Enum(vec!["Idle".into(), "Boost".into()]),
+				ChannelType::Str(8),
+			]
+		);
+		assert_eq!(data.value("armed", 1), Some(Value::Bool(true)));
+		assert_eq!(data.value("offset", 1), Some(Value::Int(-2)));
+		assert_eq!(data.value("count", 1), Some(Value::Uint(2)));
+		assert_eq!(data.enum_name("mode", 1), Some("Boost"));
+		assert_eq!(data.text("label").unwrap(), ["false", "true"]);
+		std::fs::remove_file(&path).unwrap();
+		std::fs::remove_file(path.with_extension("meta.csv")).unwrap();
+	}
+
+	#[test]
+	fn infers_csv_column_types() {
+		let path = std::env::temp_dir().join("simlib-run-data-types.csv");
+		std::fs::write(&path, "time,armed,motor\n0,false,\"I280, Cesaroni\"\n1,true,none\n")
+			.unwrap();
+
+		let data = RunData::load(&path).unwrap();
+		assert_eq!(data.types(), [ChannelType::Bool, ChannelType::Str(14)]);
+		assert_eq!(data.value("armed", 1), Some(Value::Bool(true)));
+		assert_eq!(data.text("motor").unwrap(), ["I280, Cesaroni", "none"]);
+		assert_eq!(data.column("motor"), None);
+		std::fs::remove_file(path).unwrap();
+	}
+}
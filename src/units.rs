@@ -0,0 +1,419 @@
+//! Physical quantities with units.
+//!
+//! Each quantity is a newtype over an `f64` holding its value in SI units, so mixing up quantities
+//! of different kinds is a compile error and using them costs nothing at run time. Values in other
+//! units are converted when a quantity is created or read, by name or by unit symbol, and strings
+//! like `10 ft` are parsed with [`str::parse`], which fails if the unit is not one of the quantity's.
+
+use std::error::Error;
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use crate::recordable::{Recordable, Variable};
+use crate::recorder::{ChannelType, Value};
+
+/// A physical quantity stored in SI units.
+pub trait Quantity: Copy + 'static {
+	/// The name of the kind of quantity, such as `length`.
+	const NAME: &'static str;
+	/// The symbol of the SI unit the quantity is stored and recorded in.
+	const UNIT: &'static str;
+
+	fn from_si(x: f64) -> Self;
+
+	/// Returns the value in SI units.
+	fn si(self) -> f64;
+
+	/// Creates a quantity from a value in the unit with the given symbol, such as `ft`.
+	fn from_unit(x: f64, unit: &str) -> Result<Self, UnitError>;
+
+	/// Returns the value in the unit with the given symbol.
+	fn in_unit(self, unit: &str) -> Result<f64, UnitError>;
+}
+
+/// An error from converting or parsing a quantity.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UnitError {
+	/// The string did not start with a number.
+	InvalidNumber(String),
+	/// The unit is not one of any quantity's.
+	UnknownUnit(String),
+	/// The unit belongs to a different kind of quantity.
+	WrongQuantity {
+		unit: String,
+		expected: &'static str,
+		found: &'static str,
+	},
+}
+
+impl fmt::Display for UnitError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::InvalidNumber(s) => write!(f, "`{s}` is not a number followed by a unit"),
+			Self::UnknownUnit(unit) => write!(f, "unknown unit `{unit}`"),
+			Self::WrongQuantity {
+				unit,
+				expected,
+				found,
+			} => write!(f, "expected a unit of {expected}, but `{unit}` is a unit of {found}"),
+		}
+	}
+}
+
+impl Error for UnitError {}
+
+/// A unit's symbol, and its scale and offset such that `si = x * scale + offset`.
+type UnitDef = (&'static str, f64, f64);
+
+const LENGTH_UNITS: &[UnitDef] = &[
+	("m", 1.0, 0.0),
+	("km", 1e3, 0.0),
+	("cm", 1e-2, 0.0),
+	("mm", 1e-3, 0.0),
+	("ft", 0.3048, 0.0),
+	("in", 0.0254, 0.0),
+	("yd", 0.9144, 0.0),
+	("mi", 1609.344, 0.0),
+	("nmi", 1852.0, 0.0),
+];
+const MASS_UNITS: &[UnitDef] = &[
+	("kg", 1.0, 0.0),
+	("g", 1e-3, 0.0),
+	("t", 1e3, 0.0),
+	("lb", 0.453_592_37, 0.0),
+	("slug", 14.593_902_937, 0.0),
+];
+const TIME_UNITS: &[UnitDef] = &[
+	("s", 1.0, 0.0),
+	("ms", 1e-3, 0.0),
+	("us", 1e-6, 0.0),
+	("min", 60.0, 0.0),
+	("h", 3600.0, 0.0),
+];
+const ANGLE_UNITS: &[UnitDef] = &[
+	("rad", 1.0, 0.0),
+	("deg", std::f64::consts::PI / 180.0, 0.0),
+	("°", std::f64::consts::PI / 180.0, 0.0),
+	("rev", std::f64::consts::TAU, 0.0),
+];
+const FORCE_UNITS: &[UnitDef] = &[
+	("N", 1.0, 0.0),
+	("kN", 1e3, 0.0),
+	("lbf", 4.448_221_615_260_5, 0.0),
+];
+const PRESSURE_UNITS: &[UnitDef] = &[
+	("Pa", 1.0, 0.0),
+	("kPa", 1e3, 0.0),
+	("MPa", 1e6, 0.0),
+	("bar", 1e5, 0.0),
+	("atm", 101_325.0, 0.0),
+	("psi", 6_894.757_293_168, 0.0),
+];
+const TEMPERATURE_UNITS: &[UnitDef] = &[
+	("K", 1.0, 0.0),
+	("degC", 1.0, 273.15),
+	("°C", 1.0, 273.15),
+	("degF", 5.0 / 9.0, 273.15 - 32.0 * 5.0 / 9.0),
+	("°F", 5.0 / 9.0, 273.15 - 32.0 * 5.0 / 9.0),
+];
+
+const ALL_UNITS: &[(&str, &[UnitDef])] = &[
+	("length", LENGTH_UNITS),
+	("mass", MASS_UNITS),
+	("time", TIME_UNITS),
+	("angle", ANGLE_UNITS),
+	("force", FORCE_UNITS),
+	("pressure", PRESSURE_UNITS),
+	("temperature", TEMPERATURE_UNITS),
+];
+
+/// Finds a unit of the named quantity, or explains why it is not one.
+fn find_unit(name: &'static str, units: &[UnitDef], unit: &str) -> Result<UnitDef, UnitError> {
+	if let Some(def) = units.iter().find(|(symbol, ..)| *symbol == unit) {
+		return Ok(*def);
+	}
+	let found = ALL_UNITS
+		.iter()
+		.find(|(_, units)| units.iter().any(|(symbol, ..)| *symbol == unit));
+	match found {
+		Some((found, _)) => Err(UnitError::WrongQuantity {
+			unit: unit.to_string(),
+			expected: name,
+			found,
+		}),
+		None => Err(UnitError::UnknownUnit(unit.to_string())),
+	}
+}
+
+/// Finds a unit by its symbol at compile time, for the named conversions of a quantity.
+const fn const_unit(units: &[UnitDef], symbol: &str) -> UnitDef {
+	let mut i = 0;
+	while i < units.len() {
+		let (a, b) = (units[i].0.as_bytes(), symbol.as_bytes());
+		let mut same = a.len() == b.len();
+		let mut j = 0;
+		while same && j < a.len() {
+			same = a[j] == b[j];
+			j += 1;
+		}
+		if same {
+			return units[i];
+		}
+		i += 1;
+	}
+	panic!("no such unit in the quantity's table");
+}
+
+/// Splits a string like `10 ft` or `-1.5e3m` into its number and unit.
+fn split_quantity(s: &str) -> Result<(f64, &str), UnitError> {
+	let s = s.trim();
+	// the longest prefix that is a number, so exponents are not mistaken for units
+	(1..=s.len())
+		.rev()
+		.filter(|&i| s.is_char_boundary(i))
+		.find_map(|i| Some((s[..i].trim().parse().ok()?, s[i..].trim())))
+		.ok_or_else(|| UnitError::InvalidNumber(s.to_string()))
+}
+
+macro_rules! quantity {
+	(
+		$(#[$attr:meta])*
+		$ty:ident, $name:literal, $units:ident,
+		$($from:ident, $as:ident => $symbol:literal),+ $(,)?
+	) => {
+		$(#[$attr])*
+		#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+		#[repr(transparent)]
+		pub struct $ty(f64);
+
+		impl $ty {
+			// the units are looked up at compile time, so these are plain arithmetic that folds
+			// away for SI units
+			$(
+				pub fn $from(x: f64) -> Self {
+					const UNIT: UnitDef = const_unit($units, $symbol);
+					if UNIT.2 == 0.0 {
+						Self(x * UNIT.1)
+					} else {
+						Self(x * UNIT.1 + UNIT.2)
+					}
+				}
+
+				pub fn $as(self) -> f64 {
+					const UNIT: UnitDef = const_unit($units, $symbol);
+					if UNIT.2 == 0.0 {
+						self.0 / UNIT.1
+					} else {
+						(self.0 - UNIT.2) / UNIT.1
+					}
+				}
+			)+
+		}
+
+		impl Quantity for $ty {
+			const NAME: &'static str = $name;
+			const UNIT: &'static str = $units[0].0;
+
+			fn from_si(x: f64) -> Self {
+				Self(x)
+			}
+
+			fn si(self) -> f64 {
+				self.0
+			}
+
+			fn from_unit(x: f64, unit: &str) -> Result<Self, UnitError> {
+				let (_, scale, offset) = find_unit($name, $units, unit)?;
+				Ok(Self(x * scale + offset))
+			}
+
+			fn in_unit(self, unit: &str) -> Result<f64, UnitError> {
+				let (_, scale, offset) = find_unit($name, $units, unit)?;
+				Ok((self.0 - offset) / scale)
+			}
+		}
+
+		impl FromStr for $ty {
+			type Err = UnitError;
+
+			fn from_str(s: &str) -> Result<Self, UnitError> {
+				let (x, unit) = split_quantity(s)?;
+				Self::from_unit(x, unit)
+			}
+		}
+
+		impl fmt::Display for $ty {
+			fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+				fmt::Display::fmt(&self.0, f)?;
+				write!(f, " {}", Self::UNIT)
+			}
+		}
+
+		impl Recordable for $ty {
+			fn variables() -> Vec<Variable<Self>> {
+				let variable = Variable::new(ChannelType::F64, |x: &Self| Value::F64(x.0));
+				vec![variable.with_unit(Self::UNIT)]
+			}
+		}
+	};
+}
+
+/// Implements the arithmetic of quantities that scale linearly, which temperatures do not.
+macro_rules! linear_quantity {
+	($($ty:ident),+) => {$(
+		impl Add for $ty {
+			type Output = Self;
+
+			fn add(self, rhs: Self) -> Self {
+				Self(self.0 + rhs.0)
+			}
+		}
+
+		impl Sub for $ty {
+			type Output = Self;
+
+			fn sub(self, rhs: Self) -> Self {
+				Self(self.0 - rhs.0)
+			}
+		}
+
+		impl AddAssign for $ty {
+			fn add_assign(&mut self, rhs: Self) {
+				self.0 += rhs.0;
+			}
+		}
+
+		impl SubAssign for $ty {
+			fn sub_assign(&mut self, rhs: Self) {
+				self.0 -= rhs.0;
+			}
+		}
+
+		impl Neg for $ty {
+			type Output = Self;
+
+			fn neg(self) -> Self {
+				Self(-self.0)
+			}
+		}
+
+		impl Mul<f64> for $ty {
+			type Output = Self;
+
+			fn mul(self, rhs: f64) -> Self {
+				Self(self.0 * rhs)
+			}
+		}
+
+		impl Mul<$ty> for f64 {
+			type Output = $ty;
+
+			fn mul(self, rhs: $ty) -> $ty {
+				$ty(self * rhs.0)
+			}
+		}
+
+		impl Div<f64> for $ty {
+			type Output = Self;
+
+			fn div(self, rhs: f64) -> Self {
+				Self(self.0 / rhs)
+			}
+		}
+
+		/// The ratio of two quantities of the same kind.
+		impl Div for $ty {
+			type Output = f64;
+
+			fn div(self, rhs: Self) -> f64 {
+				self.0 / rhs.0
+			}
+		}
+	)+};
+}
+
+quantity!(
+	Length, "length", LENGTH_UNITS,
+	from_meters, as_meters => "m",
+	from_kilometers, as_kilometers => "km",
+	from_feet, as_feet => "ft",
+	from_inches, as_inches => "in",
+	from_miles, as_miles => "mi",
+);
+quantity!(
+	Mass, "mass", MASS_UNITS,
+	from_kilograms, as_kilograms => "kg",
+	from_grams, as_grams => "g",
+	from_pounds, as_pounds => "lb",
+);
+quantity!(
+	Time, "time", TIME_UNITS,
+	from_seconds, as_seconds => "s",
+	from_millis, as_millis => "ms",
+	from_minutes, as_minutes => "min",
+	from_hours, as_hours => "h",
+);
+quantity!(
+	Angle, "angle", ANGLE_UNITS,
+	from_radians, as_radians => "rad",
+	from_degrees, as_degrees => "deg",
+);
+quantity!(
+	Force, "force", FORCE_UNITS,
+	from_newtons, as_newtons => "N",
+	from_kilonewtons, as_kilonewtons => "kN",
+	from_pounds_force, as_pounds_force => "lbf",
+);
+quantity!(
+	Pressure, "pressure", PRESSURE_UNITS,
+	from_pascals, as_pascals => "Pa",
+	from_kilopascals, as_kilopascals => "kPa",
+	from_bars, as_bars => "bar",
+	from_atmospheres, as_atmospheres => "atm",
+	from_psi, as_psi => "psi",
+);
+quantity!(
+	/// An absolute temperature.
+	///
+	/// Temperatures in different units have different zeros, so they cannot be added or scaled.
+	Temperature, "temperature", TEMPERATURE_UNITS,
+	from_kelvin, as_kelvin => "K",
+	from_celsius, as_celsius => "degC",
+	from_fahrenheit, as_fahrenheit => "degF",
+);
+
+linear_quantity!(Length, Mass, Time, Angle, Force, Pressure);
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn conversions() {
+		assert_eq!(Length::from_feet(10.0).as_meters(), 3.048);
+		assert!((Pressure::from_atmospheres(1.0).as_psi() - 14.695_949).abs() < 1e-6);
+		assert!((Temperature::from_celsius(100.0).as_fahrenheit() - 212.0).abs() < 1e-12);
+		assert_eq!(Temperature::from_celsius(15.0).as_kelvin(), 288.15);
+		assert_eq!(Length::from_meters(2.0) * 3.0 - Length::from_meters(1.0), Length(5.0));
+		assert_eq!(Angle::from_degrees(90.0) / Angle::from_degrees(45.0), 2.0);
+	}
+
+	#[test]
+	fn parsing_checks_units() {
+		assert_eq!("10 ft".parse(), Ok(Length::from_feet(10.0)));
+		assert_eq!("1.5e3m".parse(), Ok(Length(1500.0)));
+		assert_eq!(" 101.325 kPa ".parse(), Ok(Pressure::from_atmospheres(1.0)));
+		assert_eq!(
+			"10 kg".parse::<Length>(),
+			Err(UnitError::WrongQuantity {
+				unit: "kg".to_string(),
+				expected: "length",
+				found: "mass",
+			})
+		);
+		assert_eq!("10 parsecs".parse::<Length>(), Err(UnitError::UnknownUnit("parsecs".into())));
+		assert_eq!("10".parse::<Length>(), Err(UnitError::UnknownUnit(String::new())));
+		assert_eq!("ft".parse::<Length>(), Err(UnitError::InvalidNumber("ft".into())));
+	}
+}